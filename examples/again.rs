@@ -1,33 +1,40 @@
 use std::panic;
 
 use flexi_logger::{opt_format, Logger};
+use num_traits::Float;
 
 use vst3::BusDirection::{Input, Output};
 use vst3::BusType::Main;
 use vst3::MediaType::{Audio, Event};
-use vst3::ParameterFlag::{CanAutomate, IsBypass, IsReadOnly};
+use vst3::ParameterFlag::{CanAutomate, IsBypass};
 use vst3::ResultErr::{InvalidArgument, NotImplemented, ResultFalse};
 use vst3::ResultOk::ResOk;
 use vst3::{
-    get_channel_count, plugin_main, setup_logger, AudioProcessor, BaseAudioBus, BaseEventBus,
-    BaseParameter, BusDirection, BusInfo, BusType, BusVec, Category, ClassInfo, ClassInfoBuilder,
-    Component, ComponentHandler, EditController, FactoryInfo, FxSubcategory, HostApplication,
-    IoMode, MediaType, Parameter, ParameterContainer, ParameterInfo, ParameterInfoBuilder,
-    PlugView, PluginBase, PluginFactory, ProcessData, ProcessSetup, ResultErr, ResultOk,
-    RoutingInfo, SeekMode, Stream, SymbolicSampleSize, Unit, UnitBuilder, UnitInfo, WebPlugView,
-    NO_PROGRAM_LIST_ID, ROOT_UNIT_ID, STEREO, UID,
+    generic_process, get_channel_count, plugin_main, setup_logger, AudioProcessor, BaseAudioBus,
+    BaseEventBus, BaseParameter, BusDirection, BusInfo, BusType, BusVec, Category, ClassInfo,
+    ClassInfoBuilder, Component, EditController, FactoryInfo, FxSubcategory, GenericProcessor,
+    HostApplication, IoMode, MediaType, Meter, MeterBuilder, ParamId, Parameter,
+    ParameterContainer, ParameterInfo, ParameterInfoBuilder, PlugView, PluginBase, PluginFactory,
+    ProcessData, ProcessSetup, Program, ProgramListBuilder, ProgramListData, ResultErr, ResultOk,
+    RoutingInfo, SeekMode, Stream, UnitBuilder, UnitInfo, UnitTree, WebPlugView, ROOT_UNIT_ID,
+    STEREO, UID,
 };
 
-const GAIN_ID: usize = 0;
-const VU_PPM: usize = 1;
-const BYPASS_ID: usize = 2;
+const GAIN_ID: ParamId = ParamId(0);
+const VU_PPM: ParamId = ParamId(1);
+const BYPASS_ID: ParamId = ParamId(2);
+const PROGRAM_ID: ParamId = ParamId(3);
+
+const UNIT_1_ID: i32 = 1;
+
+const PROGRAM_LIST_ID: i32 = 1;
 
 struct GainParameter {
     inner: BaseParameter,
 }
 
 impl GainParameter {
-    fn new(flags: i32, id: usize) -> Box<Self> {
+    fn new(flags: i32, id: ParamId) -> Box<Self> {
         let info = ParameterInfoBuilder::new("Gain", id)
             .units("dB")
             .step_count(0)
@@ -50,11 +57,11 @@ impl Parameter for GainParameter {
         self.inner.get_info_mut()
     }
 
-    fn set_unit_id(&mut self, id: u32) {
+    fn set_unit_id(&mut self, id: i32) {
         self.inner.set_unit_id(id)
     }
 
-    fn get_unit_id(&self) -> u32 {
+    fn get_unit_id(&self) -> i32 {
         self.inner.get_unit_id()
     }
 
@@ -98,9 +105,8 @@ impl Parameter for GainParameter {
 
 struct AGainEditController {
     context: Option<HostApplication>,
-    units: Vec<Unit>,
+    units: UnitTree,
     parameters: ParameterContainer,
-    component_handler: Option<ComponentHandler>,
 }
 
 impl AGainEditController {
@@ -116,9 +122,8 @@ impl Default for AGainEditController {
     fn default() -> Self {
         Self {
             context: None,
-            units: vec![],
+            units: UnitTree::new(),
             parameters: ParameterContainer::new(),
-            component_handler: None,
         }
     }
 }
@@ -128,6 +133,14 @@ impl PluginBase for AGainEditController {
         Some(self)
     }
 
+    fn as_unit_info(&mut self) -> Option<&mut dyn UnitInfo> {
+        Some(self)
+    }
+
+    fn as_program_list_data(&mut self) -> Option<&mut dyn ProgramListData> {
+        Some(self)
+    }
+
     fn initialize(&mut self, context: HostApplication) -> bool {
         if self.context.is_some() {
             return false;
@@ -139,21 +152,39 @@ impl PluginBase for AGainEditController {
             log::info!("Host name: {}", name);
         }
 
-        let unit = UnitBuilder::new("Unit1", 1)
+        let program_list = ProgramListBuilder::new("Presets", PROGRAM_LIST_ID, PROGRAM_ID)
+            .unit_id(UNIT_1_ID)
+            .program(Program::new("Unity", vec![(GAIN_ID, 0.5)]))
+            .program(Program::new("Boost", vec![(GAIN_ID, 1.0)]))
+            .program(Program::new("Silence", vec![(GAIN_ID, 0.0)]))
+            .build();
+        if self
+            .units
+            .add_program_list(program_list, &mut self.parameters)
+            .is_err()
+        {
+            return false;
+        }
+
+        let unit = UnitBuilder::new("Unit1", UNIT_1_ID)
             .parent_unit_id(ROOT_UNIT_ID)
-            .program_list_id(NO_PROGRAM_LIST_ID)
+            .program_list_id(PROGRAM_LIST_ID)
             .build();
-        self.units.push(unit);
+        if self.units.add_unit(unit).is_err() {
+            return false;
+        }
 
         let gain_param = GainParameter::new(CanAutomate as i32, GAIN_ID);
         self.parameters.add_parameter(gain_param);
+        if self
+            .units
+            .assign_parameter(&mut self.parameters, GAIN_ID, UNIT_1_ID)
+            .is_err()
+        {
+            return false;
+        }
 
-        let vu_param_info = ParameterInfoBuilder::new("VuPPM", VU_PPM)
-            .step_count(0)
-            .default_normalized_value(0.0)
-            .flags(IsReadOnly as i32)
-            .build();
-        let vu_param = BaseParameter::new(vu_param_info);
+        let vu_param = Meter::create_parameter("VuPPM", VU_PPM);
         self.parameters.add_parameter(vu_param);
 
         let bypass_param_info = ParameterInfoBuilder::new("Bypass", BYPASS_ID)
@@ -169,6 +200,7 @@ impl PluginBase for AGainEditController {
 
     fn terminate(&mut self) -> bool {
         self.parameters.remove_all();
+        self.units = UnitTree::new();
 
         self.context = None;
 
@@ -177,6 +209,14 @@ impl PluginBase for AGainEditController {
 }
 
 impl EditController for AGainEditController {
+    fn get_parameters(&self) -> Option<&ParameterContainer> {
+        Some(&self.parameters)
+    }
+
+    fn get_parameters_mut(&mut self) -> Option<&mut ParameterContainer> {
+        Some(&mut self.parameters)
+    }
+
     fn set_component_state(&mut self, state: &Stream) -> Result<ResultOk, ResultErr> {
         if let Some(saved_gain) = state.read::<f64>() {
             self.set_param_normalized(GAIN_ID, saved_gain);
@@ -195,81 +235,23 @@ impl EditController for AGainEditController {
         Ok(ResOk)
     }
 
-    fn set_state(&mut self, _state: &Stream) -> Result<ResultOk, ResultErr> {
-        Ok(ResOk)
-    }
-
-    fn get_state(&self, _state: &Stream) -> Result<ResultOk, ResultErr> {
-        Ok(ResOk)
-    }
-
-    fn get_parameter_count(&self) -> Result<usize, ResultErr> {
-        Ok(self.parameters.get_parameter_count())
-    }
-
-    fn get_parameter_info(&self, param_index: usize) -> Result<&ParameterInfo, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter_by_index(param_index) {
-            return Ok(param.get_info());
-        }
-        Err(ResultFalse)
-    }
-
-    fn get_param_string_by_value(
-        &self,
-        id: usize,
-        value_normalized: f64,
-    ) -> Result<String, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter(id) {
-            return Ok(param.to_string(value_normalized));
-        }
-        Err(ResultFalse)
-    }
-
-    fn get_param_value_by_string(&self, id: usize, string: &str) -> Result<f64, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter(id) {
-            return param.from_string(string);
-        }
-        Err(ResultFalse)
-    }
-
-    fn normalized_param_to_plain(&self, id: usize, value: f64) -> Result<f64, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter(id) {
-            return Ok(param.to_plain(value));
-        }
-        Err(ResultFalse)
-    }
-
-    fn plain_param_to_normalized(&self, id: usize, plain: f64) -> Result<f64, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter(id) {
-            return Ok(param.to_normalized(plain));
-        }
-        Err(ResultFalse)
-    }
-
-    fn get_param_normalized(&self, id: usize) -> Result<f64, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter(id) {
-            return Ok(param.get_normalized());
-        }
-        Err(ResultFalse)
-    }
-
-    fn set_param_normalized(&mut self, id: usize, value: f64) -> Result<ResultOk, ResultErr> {
-        if let Some(param) = self.parameters.get_parameter_mut(id) {
-            param.set_normalized(value);
-            return Ok(ResOk);
-        }
-        Err(ResultFalse)
+    fn create_view(&mut self) -> Option<&mut Box<dyn PlugView>> {
+        None
     }
+}
 
-    fn set_component_handler(&self, _handler: ComponentHandler) -> Result<ResultOk, ResultErr> {
-        Ok(ResOk)
+impl UnitInfo for AGainEditController {
+    fn get_unit_tree(&self) -> Option<&UnitTree> {
+        Some(&self.units)
     }
 
-    fn create_view(&mut self) -> Option<&mut Box<dyn PlugView>> {
-        None
+    fn get_unit_tree_mut(&mut self) -> Option<&mut UnitTree> {
+        Some(&mut self.units)
     }
 }
 
+impl ProgramListData for AGainEditController {}
+
 struct AGainComponent {
     context: Option<HostApplication>,
     audio_inputs: BusVec,
@@ -279,6 +261,7 @@ struct AGainComponent {
     gain: f64,
     gain_reduction: f64,
     bypass: bool,
+    vu_meter: Meter,
 }
 
 impl AGainComponent {
@@ -352,6 +335,10 @@ impl Default for AGainComponent {
             gain: 1.0,
             gain_reduction: 0.0,
             bypass: false,
+            vu_meter: MeterBuilder::new(VU_PPM)
+                .release_time(0.5)
+                .rate(30.0)
+                .build(),
         }
     }
 }
@@ -516,81 +503,66 @@ impl AudioProcessor for AGainComponent {
         Some(audio_bus.get_speaker_arrangement())
     }
 
-    fn can_process_sample_size(&self, symbolic_sample_size: &SymbolicSampleSize) -> bool {
-        match symbolic_sample_size {
-            SymbolicSampleSize::Sample32 => true,
-            SymbolicSampleSize::Sample64 => false,
-        }
-    }
+    generic_process!();
 
     fn get_latency_samples(&self) -> usize {
         0
     }
 
-    fn setup_processing(&mut self, _setup: &ProcessSetup) -> bool {
+    fn setup_processing(&mut self, setup: &ProcessSetup) -> bool {
+        self.vu_meter.set_sample_rate(setup.sample_rate);
         true
     }
 
     fn set_processing(&mut self, _state: bool) -> bool {
+        self.vu_meter.reset();
         true
     }
 
-    fn process(&mut self, data: &mut ProcessData<f32>) {
-        if let Some(param_changes) = data.get_input_param_changes() {
-            let num_params_changed = param_changes.get_parameter_count();
-            for i in 0..num_params_changed {
-                if let Some(param_queue) = param_changes.get_parameter_data(i) {
-                    let num_points = param_queue.get_point_count();
-                    match param_queue.get_parameter_id() {
-                        GAIN_ID => {
-                            if let Ok(point) = param_queue.get_point(num_points - 1) {
-                                self.gain = point.value;
-                            }
-                        }
-                        BYPASS_ID => {
-                            if let Ok(point) = param_queue.get_point(num_points - 1) {
-                                self.bypass = if point.value > 0.5 { true } else { false };
-                            }
-                        }
-                        _ => (),
-                    }
+    fn get_tail_samples(&self) -> usize {
+        0
+    }
+}
+
+impl GenericProcessor for AGainComponent {
+    fn process<T: Float>(&mut self, data: &mut ProcessData<T>) {
+        let has_audio = data.num_inputs() > 0 && data.num_outputs() > 0;
+
+        for mut block in data.sub_blocks() {
+            for (id, value) in block.param_changes() {
+                match id {
+                    GAIN_ID => self.gain = value,
+                    BYPASS_ID => self.bypass = value > 0.5,
+                    _ => (),
+                }
+            }
+
+            if !has_audio {
+                continue;
+            }
+
+            let gain = T::from(self.gain).unwrap();
+            let input = block.inputs.get(0);
+            if let (Some(input), Some(mut output)) = (input, block.outputs.get_mut(0)) {
+                for pair in output.zip(&input) {
+                    pair.map(|sample| sample * gain);
                 }
             }
         }
 
-        if data.num_inputs() == 0 || data.num_outputs() == 0 {
+        if !has_audio {
             return;
         }
 
-        let mut temp = 0.0;
-
-        let (inputs, mut outputs) = data.split_buffers();
-
-        let input = inputs.get(0);
-        let mut output = outputs.get_mut(0);
-        for i in 0..input.len() {
-            let in_ = input.get(i);
-            let out_ = output.get_mut(i);
-            for (j, sample) in out_.iter_mut().enumerate() {
-                *sample = in_[j] * self.gain as f32;
-                temp = *sample;
-            }
+        let (_, mut outputs) = data.split_buffers();
+        if let Some(output) = outputs.get_mut(0) {
+            self.vu_meter.process_buffer(&output);
         }
 
         if let Some(out_param_changes) = data.get_output_param_changes_mut() {
-            let mut index = 0;
-            if let Some(param_queue) = out_param_changes.add_parameter_data(&VU_PPM, &mut index) {
-                let mut index_2 = 0;
-                param_queue.add_point(0, temp as f64, &mut index_2);
-            }
+            self.vu_meter.write(out_param_changes);
         }
     }
-
-    fn process_f64(&mut self, _data: &mut ProcessData<f64>) {}
-
-    fn get_tail_samples(&self) -> usize {
-        0
-    }
 }
 
 plugin_main!(
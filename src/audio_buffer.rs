@@ -0,0 +1,639 @@
+use std::marker::PhantomData;
+use std::slice;
+
+use num_traits::Float;
+
+use vst3_sys::vst::AudioBusBuffers;
+
+/// Input buses of a block or sub-block.
+///
+/// Hosts may process in place by passing the same buffer as input and output. Those input
+/// channels are only reachable through `OutputAudioBusBuffer::zip`, which hands out the buffer
+/// once, as `ChannelPair::InPlace`.
+pub struct Inputs<'a, T: 'a> {
+    buses: &'a [AudioBusBuffers],
+    outputs: *const AudioBusBuffers,
+    num_outputs: usize,
+    offset: usize,
+    num_samples: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Inputs<'a, T> {
+    pub(crate) fn new(
+        buses: &'a [AudioBusBuffers],
+        outputs: *const AudioBusBuffers,
+        num_outputs: usize,
+        offset: usize,
+        num_samples: usize,
+    ) -> Self {
+        Self {
+            buses,
+            outputs,
+            num_outputs,
+            offset,
+            num_samples,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buses.is_empty()
+    }
+
+    /// `true` if every channel of every bus is flagged as silent.
+    pub fn is_silent(&self) -> bool {
+        self.buses.iter().all(|bus| {
+            let mask = silence_mask(bus.num_channels as usize);
+            bus.silence_flags & mask == mask
+        })
+    }
+
+    pub fn get(&self, index: usize) -> Option<InputAudioBusBuffer<'a, T>> {
+        let bus = self.buses.get(index)?;
+        Some(InputAudioBusBuffer {
+            silence_flags: bus.silence_flags,
+            channels: unsafe { channel_pointers(bus) },
+            outputs: self.outputs,
+            num_outputs: self.num_outputs,
+            offset: self.offset,
+            num_samples: self.num_samples,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = InputAudioBusBuffer<'a, T>> + '_ {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+}
+
+pub struct InputAudioBusBuffer<'a, T: 'a> {
+    silence_flags: u64,
+    channels: &'a [*mut T],
+    outputs: *const AudioBusBuffers,
+    num_outputs: usize,
+    offset: usize,
+    num_samples: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> InputAudioBusBuffer<'a, T> {
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+
+    /// `None` if the channel does not exist or shares its buffer with an output channel.
+    pub fn get(&self, index: usize) -> Option<&'a [T]> {
+        let channel = *self.channels.get(index)?;
+        if self.is_output(channel) {
+            return None;
+        }
+        unsafe {
+            Some(slice::from_raw_parts(
+                channel.add(self.offset),
+                self.num_samples,
+            ))
+        }
+    }
+
+    /// `true` if the host passed the same buffer for the channel and an output channel.
+    pub fn is_in_place(&self, index: usize) -> bool {
+        match self.channels.get(index) {
+            Some(channel) => self.is_output(*channel),
+            None => false,
+        }
+    }
+
+    /// The channels in order, `None` standing for in place channels.
+    pub fn iter(&self) -> impl Iterator<Item = Option<&'a [T]>> + '_ {
+        (0..self.len()).map(move |index| self.get(index))
+    }
+
+    /// Iterates over the samples at the same position in all channels.
+    pub fn frames(&self) -> Frames<'_, 'a, T> {
+        Frames {
+            bus: self,
+            index: 0,
+        }
+    }
+
+    /// Bit `n` is set if the host flagged channel `n` as silent.
+    pub fn get_silence_flags(&self) -> u64 {
+        self.silence_flags
+    }
+
+    /// Channels above 63 are never flagged.
+    pub fn is_channel_silent(&self, channel: usize) -> bool {
+        channel < 64 && self.silence_flags & (1 << channel) != 0
+    }
+
+    fn is_output(&self, channel: *mut T) -> bool {
+        unsafe { is_output_channel(self.outputs, self.num_outputs, channel) }
+    }
+}
+
+/// Output buses of a block or sub-block. Buses are borrowed mutably one at a time.
+pub struct Outputs<'a, T: 'a> {
+    buses: *mut AudioBusBuffers,
+    num_buses: usize,
+    offset: usize,
+    num_samples: usize,
+    block_samples: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Outputs<'a, T> {
+    pub(crate) fn new(
+        buses: *mut AudioBusBuffers,
+        num_buses: usize,
+        offset: usize,
+        num_samples: usize,
+        block_samples: usize,
+    ) -> Self {
+        Self {
+            buses,
+            num_buses,
+            offset,
+            num_samples,
+            block_samples,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_buses
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_buses == 0
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<OutputAudioBusBuffer<'_, T>> {
+        if index >= self.num_buses {
+            return None;
+        }
+        unsafe {
+            let bus = self.buses.add(index);
+            Some(OutputAudioBusBuffer {
+                silence_flags: &mut (*bus).silence_flags,
+                channels: channel_pointers(&*bus),
+                offset: self.offset,
+                num_samples: self.num_samples,
+                whole_block: self.offset == 0 && self.num_samples == self.block_samples,
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
+pub struct OutputAudioBusBuffer<'b, T: 'b> {
+    silence_flags: *mut u64,
+    channels: &'b [*mut T],
+    offset: usize,
+    num_samples: usize,
+    whole_block: bool,
+    _marker: PhantomData<&'b mut T>,
+}
+
+impl<'b, T> OutputAudioBusBuffer<'b, T> {
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[T]> {
+        let channel = *self.channels.get(index)?;
+        unsafe {
+            Some(slice::from_raw_parts(
+                channel.add(self.offset),
+                self.num_samples,
+            ))
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut [T]> {
+        let channel = *self.channels.get(index)?;
+        unsafe {
+            Some(slice::from_raw_parts_mut(
+                channel.add(self.offset),
+                self.num_samples,
+            ))
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[T]> + '_ {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+
+    /// Channels sharing their buffer with an earlier channel are skipped, so that no samples
+    /// are borrowed twice.
+    pub fn iter_mut(&mut self) -> ChannelsMut<'_, T> {
+        ChannelsMut {
+            channels: self.channels,
+            index: 0,
+            offset: self.offset,
+            num_samples: self.num_samples,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Iterates over the samples at the same position in all channels. As in `iter_mut`,
+    /// channels sharing the buffer of an earlier channel are skipped.
+    pub fn frames_mut(&mut self) -> FramesMut<'_, T> {
+        FramesMut {
+            channels: self.channels,
+            offset: self.offset,
+            index: 0,
+            num_samples: self.num_samples,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Pairs every channel with the input channel of the same index. As in `iter_mut`,
+    /// channels sharing the buffer of an earlier channel are skipped.
+    pub fn zip<'c>(&'c mut self, input: &InputAudioBusBuffer<'c, T>) -> ChannelPairs<'c, T> {
+        ChannelPairs {
+            outputs: self.channels,
+            inputs: input.channels,
+            all_outputs: input.outputs,
+            num_all_outputs: input.num_outputs,
+            index: 0,
+            offset: self.offset,
+            num_samples: self.num_samples,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn get_silence_flags(&self) -> u64 {
+        unsafe { *self.silence_flags }
+    }
+
+    /// Tells the host which channels are silent, bit `n` standing for channel `n`. The flags
+    /// describe the whole block, so when processing sub-blocks only flag the channels that are
+    /// silent in all of them.
+    pub fn set_silence_flags(&mut self, flags: u64) {
+        unsafe { *self.silence_flags = flags }
+    }
+}
+
+impl<'b, T: Float> OutputAudioBusBuffer<'b, T> {
+    /// Zeroes the channel and flags it as silent. Hosts may ignore the flags, so the samples
+    /// are written either way. The flags describe the whole block, so a sub-block shorter than
+    /// the block only zeroes its samples.
+    pub fn mark_silent(&mut self, channel: usize) {
+        if let Some(samples) = self.get_mut(channel) {
+            for sample in samples.iter_mut() {
+                *sample = T::zero();
+            }
+            if channel < 64 && self.whole_block {
+                unsafe { *self.silence_flags |= 1 << channel }
+            }
+        }
+    }
+}
+
+pub struct ChannelsMut<'b, T: 'b> {
+    channels: &'b [*mut T],
+    index: usize,
+    offset: usize,
+    num_samples: usize,
+    _marker: PhantomData<&'b mut T>,
+}
+
+impl<'b, T> Iterator for ChannelsMut<'b, T> {
+    type Item = &'b mut [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = next_distinct(self.channels, &mut self.index)?;
+        unsafe {
+            Some(slice::from_raw_parts_mut(
+                channel.add(self.offset),
+                self.num_samples,
+            ))
+        }
+    }
+}
+
+pub struct Frames<'b, 'a, T: 'a> {
+    bus: &'b InputAudioBusBuffer<'a, T>,
+    index: usize,
+}
+
+impl<'b, 'a, T> Iterator for Frames<'b, 'a, T> {
+    type Item = Frame<'b, 'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.bus.num_samples {
+            return None;
+        }
+        let frame = Frame {
+            bus: self.bus,
+            channel: 0,
+            position: self.bus.offset + self.index,
+        };
+        self.index += 1;
+        Some(frame)
+    }
+}
+
+/// The samples of one input frame, `None` standing for in place channels.
+pub struct Frame<'b, 'a, T: 'a> {
+    bus: &'b InputAudioBusBuffer<'a, T>,
+    channel: usize,
+    position: usize,
+}
+
+impl<'b, 'a, T> Iterator for Frame<'b, 'a, T> {
+    type Item = Option<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = *self.bus.channels.get(self.channel)?;
+        self.channel += 1;
+        if self.bus.is_output(channel) {
+            return Some(None);
+        }
+        unsafe { Some(Some(&*channel.add(self.position))) }
+    }
+}
+
+pub struct FramesMut<'b, T: 'b> {
+    channels: &'b [*mut T],
+    offset: usize,
+    index: usize,
+    num_samples: usize,
+    _marker: PhantomData<&'b mut T>,
+}
+
+impl<'b, T> Iterator for FramesMut<'b, T> {
+    type Item = FrameMut<'b, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.num_samples {
+            return None;
+        }
+        let frame = FrameMut {
+            channels: self.channels,
+            index: 0,
+            position: self.offset + self.index,
+            _marker: PhantomData,
+        };
+        self.index += 1;
+        Some(frame)
+    }
+}
+
+/// The samples of one frame, one per channel.
+pub struct FrameMut<'b, T: 'b> {
+    channels: &'b [*mut T],
+    index: usize,
+    position: usize,
+    _marker: PhantomData<&'b mut T>,
+}
+
+impl<'b, T> Iterator for FrameMut<'b, T> {
+    type Item = &'b mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = next_distinct(self.channels, &mut self.index)?;
+        unsafe { Some(&mut *channel.add(self.position)) }
+    }
+}
+
+pub enum ChannelPair<'b, T: 'b> {
+    Separate {
+        input: &'b [T],
+        output: &'b mut [T],
+    },
+    /// The host passed the same buffer, which holds the input until it is overwritten.
+    InPlace(&'b mut [T]),
+    /// There is no input channel to read, the output has to be generated.
+    OutputOnly(&'b mut [T]),
+    /// The host passed the buffer of another output channel as input, so the input cannot be
+    /// read without aliasing that output and may already be overwritten. Plug-ins that need it
+    /// have to copy the inputs of the bus before writing any output.
+    SharedInput(&'b mut [T]),
+}
+
+impl<'b, T: Float> ChannelPair<'b, T> {
+    /// Writes `f(input)` to every output sample, with silence as input for `OutputOnly` and
+    /// `SharedInput`.
+    pub fn map(self, mut f: impl FnMut(T) -> T) {
+        match self {
+            ChannelPair::Separate { input, output } => {
+                for (output, input) in output.iter_mut().zip(input.iter()) {
+                    *output = f(*input);
+                }
+            }
+            ChannelPair::InPlace(samples) => {
+                for sample in samples.iter_mut() {
+                    *sample = f(*sample);
+                }
+            }
+            ChannelPair::OutputOnly(output) | ChannelPair::SharedInput(output) => {
+                for sample in output.iter_mut() {
+                    *sample = f(T::zero());
+                }
+            }
+        }
+    }
+}
+
+pub struct ChannelPairs<'b, T: 'b> {
+    outputs: &'b [*mut T],
+    inputs: &'b [*mut T],
+    all_outputs: *const AudioBusBuffers,
+    num_all_outputs: usize,
+    index: usize,
+    offset: usize,
+    num_samples: usize,
+    _marker: PhantomData<&'b mut T>,
+}
+
+impl<'b, T> Iterator for ChannelPairs<'b, T> {
+    type Item = ChannelPair<'b, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let output = next_distinct(self.outputs, &mut self.index)?;
+        let input = self.inputs.get(self.index - 1).copied();
+        unsafe {
+            let output_samples =
+                slice::from_raw_parts_mut(output.add(self.offset), self.num_samples);
+            Some(match input {
+                Some(input) if input == output => ChannelPair::InPlace(output_samples),
+                Some(input) if is_output_channel(self.all_outputs, self.num_all_outputs, input) => {
+                    ChannelPair::SharedInput(output_samples)
+                }
+                Some(input) => ChannelPair::Separate {
+                    input: slice::from_raw_parts(input.add(self.offset), self.num_samples),
+                    output: output_samples,
+                },
+                None => ChannelPair::OutputOnly(output_samples),
+            })
+        }
+    }
+}
+
+/// The channel at `index` or the next one not sharing its buffer with an earlier channel.
+/// `index` is left after the returned channel.
+fn next_distinct<T>(channels: &[*mut T], index: &mut usize) -> Option<*mut T> {
+    while let Some(channel) = channels.get(*index).copied() {
+        *index += 1;
+        if !channels[..*index - 1].contains(&channel) {
+            return Some(channel);
+        }
+    }
+    None
+}
+
+pub(crate) fn silence_mask(num_channels: usize) -> u64 {
+    if num_channels >= 64 {
+        u64::MAX
+    } else {
+        (1 << num_channels) - 1
+    }
+}
+
+unsafe fn channel_pointers<'a, T>(bus: &AudioBusBuffers) -> &'a [*mut T] {
+    if bus.buffers.is_null() || bus.num_channels <= 0 {
+        return &[];
+    }
+    slice::from_raw_parts(bus.buffers as *const *mut T, bus.num_channels as usize)
+}
+
+unsafe fn is_output_channel<T>(
+    outputs: *const AudioBusBuffers,
+    num_outputs: usize,
+    channel: *mut T,
+) -> bool {
+    if outputs.is_null() {
+        return false;
+    }
+    slice::from_raw_parts(outputs, num_outputs)
+        .iter()
+        .any(|bus| channel_pointers::<T>(bus).contains(&channel))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::raw::c_void;
+
+    use vst3_sys::vst::AudioBusBuffers;
+
+    use crate::{ChannelPair, Inputs, Outputs};
+
+    fn bus(pointers: &mut [*mut f32]) -> AudioBusBuffers {
+        AudioBusBuffers {
+            num_channels: pointers.len() as i32,
+            silence_flags: 0,
+            buffers: pointers.as_mut_ptr() as *mut *mut c_void,
+        }
+    }
+
+    #[test]
+    fn test_in_place_channels() {
+        let mut output = [[0.0f32; 4]; 3];
+        let mut input = [1.0f32; 4];
+        let mut output_pointers: Vec<*mut f32> = output
+            .iter_mut()
+            .map(|channel| channel.as_mut_ptr())
+            .collect();
+        // Channel 0 is processed in place, channel 2 shares the buffer of output channel 1.
+        let mut input_pointers = [output_pointers[0], input.as_mut_ptr(), output_pointers[1]];
+        let inputs = [bus(&mut input_pointers)];
+        let mut outputs = [bus(&mut output_pointers)];
+        let outputs = outputs.as_mut_ptr();
+
+        let inputs = Inputs::<f32>::new(&inputs, outputs, 1, 0, 4);
+        let input = inputs.get(0).unwrap();
+        assert!(input.get(0).is_none() && input.is_in_place(0));
+        assert_eq!(input.get(1), Some(&[1.0; 4][..]));
+        assert!(input.get(2).is_none());
+        let frame: Vec<Option<f32>> = input.frames().next().unwrap().map(|s| s.copied()).collect();
+        assert_eq!(frame, [None, Some(1.0), None]);
+
+        let mut outputs = Outputs::<f32>::new(outputs, 1, 0, 4, 4);
+        let mut output = outputs.get_mut(0).unwrap();
+        let pairs: Vec<&str> = output
+            .zip(&input)
+            .map(|pair| match pair {
+                ChannelPair::InPlace(_) => "in place",
+                ChannelPair::Separate { .. } => "separate",
+                ChannelPair::OutputOnly(_) => "output only",
+                ChannelPair::SharedInput(_) => "shared input",
+            })
+            .collect();
+        assert_eq!(pairs, ["in place", "separate", "shared input"]);
+    }
+
+    #[test]
+    fn test_frames_and_sub_block_offsets() {
+        let mut output = [[1.0f32; 4]; 2];
+        let mut output_pointers: Vec<*mut f32> = output
+            .iter_mut()
+            .map(|channel| channel.as_mut_ptr())
+            .collect();
+        let mut outputs = [bus(&mut output_pointers)];
+        let outputs = outputs.as_mut_ptr();
+
+        // The second half of the block.
+        let mut sub_block = Outputs::<f32>::new(outputs, 1, 2, 2, 4);
+        let mut bus = sub_block.get_mut(0).unwrap();
+        for (index, frame) in bus.frames_mut().enumerate() {
+            for sample in frame {
+                *sample = index as f32 + 2.0;
+            }
+        }
+        bus.mark_silent(1);
+        assert_eq!(bus.get_silence_flags(), 0);
+
+        let mut block = Outputs::<f32>::new(outputs, 1, 0, 4, 4);
+        let mut bus = block.get_mut(0).unwrap();
+        assert_eq!(bus.get(0), Some(&[1.0, 1.0, 2.0, 3.0][..]));
+        assert_eq!(bus.get(1), Some(&[1.0, 1.0, 0.0, 0.0][..]));
+        bus.mark_silent(1);
+        assert_eq!(bus.get_silence_flags(), 0b10);
+    }
+
+    #[test]
+    fn test_repeated_output_channels() {
+        let mut output = [[0.0f32; 2]; 2];
+        let mut input = [1.0f32; 2];
+        let (first, second) = (output[0].as_mut_ptr(), output[1].as_mut_ptr());
+        let mut output_pointers = [first, first, second];
+        let mut input_pointers = [input.as_mut_ptr()];
+        let inputs = [bus(&mut input_pointers)];
+        let mut outputs = [bus(&mut output_pointers)];
+        let outputs = outputs.as_mut_ptr();
+
+        let inputs = Inputs::<f32>::new(&inputs, outputs, 1, 0, 2);
+        let input = inputs.get(0).unwrap();
+        let mut outputs = Outputs::<f32>::new(outputs, 1, 0, 2, 2);
+        let mut output = outputs.get_mut(0).unwrap();
+        assert_eq!(output.iter_mut().count(), 2);
+        assert_eq!(output.frames_mut().next().unwrap().count(), 2);
+        let pairs: Vec<bool> = output
+            .zip(&input)
+            .map(|pair| matches!(pair, ChannelPair::Separate { .. }))
+            .collect();
+        assert_eq!(pairs, [true, false]);
+    }
+}
@@ -1,17 +1,20 @@
 use std::marker::PhantomData;
 use std::os::raw::c_void;
 use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use num_traits::Float;
 
 use vst3_sys::vst::ProcessModes::{kOffline, kPrefetch, kRealtime};
 use vst3_sys::vst::{AudioBusBuffers, IAudioProcessor, SymbolicSampleSizes};
 
+use crate::sample_converter::{bus_channels, SampleConverter};
+use crate::sub_block::{collect_points, SubBlockPoint};
 use crate::ResultErr::{InternalError, InvalidArgument, NotImplemented, ResultFalse};
 use crate::ResultOk::ResOk;
 use crate::{
-    register_panic_msg, BusDirection, Component, EventList, ParameterChanges, ResultErr, ResultOk,
-    Unknown, VST3Component,
+    register_panic_msg, silence_mask, BusDirection, Component, EventList, Inputs, Outputs,
+    ParameterChanges, ProcessContext, ResultErr, ResultOk, SubBlocks, Unknown, VST3Component,
 };
 use std::sync::Mutex;
 
@@ -43,7 +46,10 @@ impl From<i32> for SymbolicSampleSize {
     }
 }
 
-#[derive(Debug)]
+/// `kInfiniteTail`.
+const INFINITE_TAIL: usize = u32::MAX as usize;
+
+#[derive(Copy, Clone, Debug)]
 pub enum ProcessMode {
     Realtime,
     Prefetch,
@@ -89,33 +95,66 @@ pub struct ProcessData<'a, T: 'a + Float> {
     out_param_changes: Option<Box<ParameterChanges>>,
     in_events: Option<Box<EventList>>,
     out_events: Option<Box<EventList>>,
+    process_context: Option<ProcessContext>,
+    sub_block_points: Vec<SubBlockPoint>,
     _marker: PhantomData<T>,
 }
 
 impl<'a, T: 'a + Float> ProcessData<'a, T> {
     #[inline]
-    pub(crate) unsafe fn from_raw(
-        num_inputs: usize,
-        num_outputs: usize,
-        inputs_raw: *const AudioBusBuffers,
-        outputs_raw: *mut AudioBusBuffers,
+    pub(crate) unsafe fn from_raw(data: &'a mut vst3_sys::vst::ProcessData) -> Self {
+        let inputs = slice::from_raw_parts(data.inputs as *const _, data.num_inputs as usize);
+        let outputs = slice::from_raw_parts_mut(data.outputs, data.num_outputs as usize);
+        Self::with_buses(data, inputs, outputs)
+    }
+
+    /// Takes the parameter changes, events and context from `data` but processes the given
+    /// buses instead of the host's, e.g. converted copies of them.
+    pub(crate) unsafe fn with_buses(
+        data: &vst3_sys::vst::ProcessData,
+        inputs: &'a [AudioBusBuffers],
+        outputs: &'a mut [AudioBusBuffers],
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            process_mode: ProcessMode::from(data.process_mode),
+            num_samples: data.num_samples.max(0) as usize,
+            in_param_changes: ParameterChanges::from_raw(
+                data.input_parameter_changes as *mut c_void,
+            ),
+            out_param_changes: ParameterChanges::from_raw(
+                data.output_parameter_changes as *mut c_void,
+            ),
+            _marker: PhantomData,
+            in_events: EventList::from_raw(data.input_events as *mut c_void),
+            out_events: EventList::from_raw(data.output_events as *mut c_void),
+            process_context: data.process_context.as_ref().map(ProcessContext::from),
+            sub_block_points: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_parts(
+        inputs: &'a [AudioBusBuffers],
+        outputs: &'a mut [AudioBusBuffers],
         process_mode: ProcessMode,
         num_samples: usize,
-        in_param_ptr: *mut c_void,
-        out_param_ptr: *mut c_void,
-        in_events_ptr: *mut c_void,
-        out_events_ptr: *mut c_void,
+        in_events: Option<Box<EventList>>,
+        out_events: Option<Box<EventList>>,
+        process_context: Option<ProcessContext>,
     ) -> Self {
         Self {
-            inputs: slice::from_raw_parts(inputs_raw, num_inputs),
-            outputs: slice::from_raw_parts_mut(outputs_raw, num_outputs),
+            inputs,
+            outputs,
             process_mode,
             num_samples,
-            in_param_changes: ParameterChanges::from_raw(in_param_ptr),
-            out_param_changes: ParameterChanges::from_raw(out_param_ptr),
+            in_param_changes: None,
+            out_param_changes: None,
             _marker: PhantomData,
-            in_events: EventList::from_raw(in_events_ptr),
-            out_events: EventList::from_raw(out_events_ptr),
+            in_events,
+            out_events,
+            process_context,
+            sub_block_points: Vec::new(),
         }
     }
 
@@ -134,25 +173,52 @@ impl<'a, T: 'a + Float> ProcessData<'a, T> {
         self.num_samples
     }
 
+    /// The outputs borrow the block mutably, so their channels cannot alias any other slice.
     #[inline]
-    pub fn split_buffers<'b>(&'b self) -> (Inputs<'b, T>, Outputs<'b, T>)
-    where
-        'a: 'b,
-    {
+    pub fn split_buffers(&mut self) -> (Inputs<'_, T>, Outputs<'_, T>) {
+        let outputs = self.outputs.as_mut_ptr();
         (
-            Inputs {
-                buffers: self.inputs,
-                num_samples: self.num_samples,
-                _marker: PhantomData,
-            },
-            Outputs {
-                buffers: self.outputs,
-                num_samples: self.num_samples,
-                _marker: PhantomData,
-            },
+            Inputs::new(
+                self.inputs,
+                outputs,
+                self.outputs.len(),
+                0,
+                self.num_samples,
+            ),
+            Outputs::new(
+                outputs,
+                self.outputs.len(),
+                0,
+                self.num_samples,
+                self.num_samples,
+            ),
         )
     }
 
+    /// Splits the block at every sample offset of the input events and parameter changes.
+    pub fn sub_blocks(&mut self) -> SubBlocks<'_, T> {
+        collect_points(
+            &mut self.sub_block_points,
+            self.num_samples,
+            self.in_events.as_deref(),
+            self.in_param_changes.as_deref(),
+        );
+        SubBlocks::new(
+            self.inputs,
+            self.outputs.as_mut_ptr(),
+            self.outputs.len(),
+            self.num_samples,
+            self.in_events.as_deref(),
+            &self.sub_block_points,
+        )
+    }
+
+    /// Exchanges the buffer `sub_blocks` sorts the boundaries in, so that the one preallocated
+    /// by the component is used.
+    pub(crate) fn swap_sub_block_points(&mut self, points: &mut Vec<SubBlockPoint>) {
+        std::mem::swap(&mut self.sub_block_points, points);
+    }
+
     pub fn get_input_param_changes(&self) -> Option<&Box<ParameterChanges>> {
         self.in_param_changes.as_ref()
     }
@@ -168,97 +234,77 @@ impl<'a, T: 'a + Float> ProcessData<'a, T> {
     pub fn get_output_events_mut(&mut self) -> Option<&mut Box<EventList>> {
         self.out_events.as_mut()
     }
-}
 
-pub struct Inputs<'a, T: 'a> {
-    buffers: &'a [AudioBusBuffers],
-    num_samples: usize,
-    _marker: PhantomData<T>,
-}
-
-impl<'a, T> Inputs<'a, T> {
-    pub fn len(&self) -> usize {
-        self.buffers.len()
+    /// `true` if the host flagged every channel of every input bus as silent and there are no
+    /// input events or parameter changes.
+    pub fn is_input_silent(&self) -> bool {
+        !self.inputs.is_empty()
+            && self.inputs.iter().all(|bus| {
+                let mask = silence_mask(bus.num_channels as usize);
+                bus.silence_flags & mask == mask
+            })
+            && self
+                .in_events
+                .as_ref()
+                .map(|events| events.get_event_count())
+                .unwrap_or(0)
+                == 0
+            && self
+                .in_param_changes
+                .as_ref()
+                .map(|changes| changes.get_parameter_count())
+                .unwrap_or(0)
+                == 0
     }
 
-    pub fn get(&self, index: usize) -> InputAudioBusBuffer<'a, T> {
-        unsafe {
-            InputAudioBusBuffer {
-                silence_flags: self.buffers[index].silence_flags,
-                buffers: slice::from_raw_parts(
-                    self.buffers[index].buffers as *const *const T,
-                    self.buffers[index].num_channels as usize,
-                ),
-                num_samples: self.num_samples,
+    /// Zeroes every output channel and flags it as silent.
+    pub fn clear_outputs(&mut self) {
+        let (_, mut outputs) = self.split_buffers();
+        for index in 0..outputs.len() {
+            if let Some(mut bus) = outputs.get_mut(index) {
+                for channel in 0..bus.len() {
+                    bus.mark_silent(channel);
+                }
             }
         }
     }
-}
-
-pub struct InputAudioBusBuffer<'a, T: 'a> {
-    silence_flags: u64,
-    buffers: &'a [*const T],
-    num_samples: usize,
-}
-
-impl<'a, T> InputAudioBusBuffer<'a, T> {
-    pub fn len(&self) -> usize {
-        self.buffers.len()
-    }
 
-    pub fn get(&self, index: usize) -> &'a [T] {
-        unsafe { slice::from_raw_parts(self.buffers[index], self.num_samples) }
+    /// `None` if the host did not provide one, e.g. when processing without a transport.
+    pub fn process_context(&self) -> Option<&ProcessContext> {
+        self.process_context.as_ref()
     }
 }
 
-pub struct Outputs<'a, T: 'a> {
-    buffers: &'a [AudioBusBuffers],
-    num_samples: usize,
-    _marker: PhantomData<T>,
+/// Lends the preallocated sub-block buffer to `data` while it is processed.
+fn with_sub_block_points<T: Float>(
+    data: &mut ProcessData<T>,
+    points: &Mutex<Vec<SubBlockPoint>>,
+    process: impl FnOnce(&mut ProcessData<T>),
+) {
+    let mut points = points.lock().unwrap();
+    data.swap_sub_block_points(&mut points);
+    process(data);
+    data.swap_sub_block_points(&mut points);
 }
 
-impl<'a, T> Outputs<'a, T> {
-    pub fn len(&self) -> usize {
-        self.buffers.len()
+/// Zeroes the outputs instead of processing once the inputs have been silent for longer than
+/// the tail. Returns `true` if the block was skipped.
+fn skip_silent_block<T: Float>(
+    data: &mut ProcessData<T>,
+    silent_samples: &AtomicUsize,
+    tail_samples: usize,
+) -> bool {
+    if !data.is_input_silent() {
+        silent_samples.store(0, Ordering::Relaxed);
+        return false;
     }
-
-    pub fn get(&self, index: usize) -> OutputAudioBusBuffer<'a, T> {
-        unsafe {
-            OutputAudioBusBuffer {
-                silence_flags: self.buffers[index].silence_flags,
-                buffers: slice::from_raw_parts(
-                    self.buffers[index].buffers as *mut *mut T,
-                    self.buffers[index].num_channels as usize,
-                ),
-                num_samples: self.num_samples,
-            }
-        }
-    }
-
-    pub fn get_mut(&mut self, index: usize) -> OutputAudioBusBuffer<'a, T> {
-        unsafe {
-            OutputAudioBusBuffer {
-                silence_flags: self.buffers[index].silence_flags,
-                buffers: slice::from_raw_parts_mut(
-                    self.buffers[index].buffers as *mut *mut T,
-                    self.buffers[index].num_channels as usize,
-                ),
-                num_samples: self.num_samples,
-            }
-        }
-    }
-}
-
-pub struct OutputAudioBusBuffer<'a, T: 'a> {
-    silence_flags: u64,
-    buffers: &'a [*mut T],
-    num_samples: usize,
-}
-
-impl<'a, T> OutputAudioBusBuffer<'a, T> {
-    pub fn get_mut(&mut self, index: usize) -> &'a mut [T] {
-        unsafe { slice::from_raw_parts_mut(self.buffers[index], self.num_samples) }
+    let silent = silent_samples.load(Ordering::Relaxed);
+    silent_samples.store(silent.saturating_add(data.num_samples()), Ordering::Relaxed);
+    if tail_samples >= INFINITE_TAIL || silent < tail_samples {
+        return false;
     }
+    data.clear_outputs();
+    true
 }
 
 pub trait AudioProcessor: Component {
@@ -271,6 +317,27 @@ pub trait AudioProcessor: Component {
     fn process(&mut self, data: &mut ProcessData<f32>);
     fn process_f64(&mut self, data: &mut ProcessData<f64>);
     fn get_tail_samples(&self) -> usize;
+
+    /// When `true`, `process` is not called for blocks whose inputs are silent once the tail
+    /// has run out, and the outputs are cleared instead. Effects only: there must be at least
+    /// one input bus.
+    fn skip_silence(&self) -> bool {
+        false
+    }
+
+    /// When `true`, 64-bit processing is accepted for plug-ins that only process f32: the host
+    /// buffers are converted to f32 buffers allocated in `setup_processing` and `process` is
+    /// called with them. `setup_processing` then sees `Sample32`.
+    fn process_f64_as_f32(&self) -> bool {
+        false
+    }
+}
+
+/// A single `process` for both sample sizes. Implement it and call `generic_process!()` inside
+/// the `impl AudioProcessor` block in place of `can_process_sample_size`, `process` and
+/// `process_f64`.
+pub trait GenericProcessor {
+    fn process<T: Float>(&mut self, data: &mut ProcessData<T>);
 }
 
 impl IAudioProcessor for VST3Component {
@@ -349,8 +416,10 @@ impl IAudioProcessor for VST3Component {
                     if !SymbolicSampleSize::is_valid(symbolic_sample_size) {
                         return *ret.lock().unwrap() = InvalidArgument.into();
                     }
-                    return if audio_processor
-                        .can_process_sample_size(&SymbolicSampleSize::from(symbolic_sample_size))
+                    let symbolic_sample_size = SymbolicSampleSize::from(symbolic_sample_size);
+                    return if audio_processor.can_process_sample_size(&symbolic_sample_size)
+                        || (matches!(symbolic_sample_size, SymbolicSampleSize::Sample64)
+                            && audio_processor.process_f64_as_f32())
                     {
                         *ret.lock().unwrap() = ResOk.into()
                     } else {
@@ -402,6 +471,7 @@ impl IAudioProcessor for VST3Component {
 
     unsafe fn setup_processing(&self, setup: *const vst3_sys::vst::ProcessSetup) -> i32 {
         let mutex_plugin_base = self.get_plugin_base();
+        let sample_converter = self.get_sample_converter();
         let ret: Mutex<i32> = Mutex::new(InternalError.into());
         match std::panic::catch_unwind(|| {
             if let Ok(mut plugin_base) = mutex_plugin_base.lock() {
@@ -409,7 +479,23 @@ impl IAudioProcessor for VST3Component {
                     if setup.is_null() {
                         return *ret.lock().unwrap() = InvalidArgument.into();
                     }
-                    return if audio_processor.setup_processing(&ProcessSetup::from(*setup)) {
+                    let mut setup = ProcessSetup::from(*setup);
+                    let convert =
+                        matches!(setup.symbolic_sample_size, SymbolicSampleSize::Sample64)
+                            && audio_processor.process_f64_as_f32();
+                    if convert {
+                        setup.symbolic_sample_size = SymbolicSampleSize::Sample32;
+                    }
+                    return if audio_processor.setup_processing(&setup) {
+                        *sample_converter.lock().unwrap() = if convert {
+                            Some(SampleConverter::new(
+                                setup.max_samples_per_block.max(0) as usize,
+                                &bus_channels(&*audio_processor, BusDirection::Input),
+                                &bus_channels(&*audio_processor, BusDirection::Output),
+                            ))
+                        } else {
+                            None
+                        };
                         *ret.lock().unwrap() = ResOk.into()
                     } else {
                         *ret.lock().unwrap() = ResultFalse.into()
@@ -429,6 +515,8 @@ impl IAudioProcessor for VST3Component {
 
     unsafe fn set_processing(&self, state: u8) -> i32 {
         let mutex_plugin_base = self.get_plugin_base();
+        // A silent stretch does not carry over to the next processing run.
+        self.get_silent_samples().store(0, Ordering::Relaxed);
         let ret: Mutex<i32> = Mutex::new(InternalError.into());
         match std::panic::catch_unwind(|| {
             if let Ok(mut plugin_base) = mutex_plugin_base.lock() {
@@ -454,6 +542,9 @@ impl IAudioProcessor for VST3Component {
 
     unsafe fn process(&self, data: *mut vst3_sys::vst::ProcessData) -> i32 {
         let mutex_plugin_base = self.get_plugin_base();
+        let silent_samples = self.get_silent_samples();
+        let sample_converter = self.get_sample_converter();
+        let sub_block_points = self.get_sub_block_points();
         let ret: Mutex<i32> = Mutex::new(InternalError.into());
         match std::panic::catch_unwind(|| {
             if let Ok(mut plugin_base) = mutex_plugin_base.lock() {
@@ -461,40 +552,50 @@ impl IAudioProcessor for VST3Component {
                     if data.is_null() {
                         return *ret.lock().unwrap() = InvalidArgument.into();
                     }
+                    let tail_samples = if audio_processor.skip_silence() {
+                        audio_processor.get_tail_samples()
+                    } else {
+                        INFINITE_TAIL
+                    };
                     return match SymbolicSampleSize::from((*data).symbolic_sample_size) {
                         SymbolicSampleSize::Sample32 => {
-                            let mut process_data = ProcessData::<f32>::from_raw(
-                                (*data).num_inputs as usize,
-                                (*data).num_outputs as usize,
-                                (*data).inputs as *const AudioBusBuffers,
-                                (*data).outputs,
-                                ProcessMode::from((*data).process_mode),
-                                (*data).num_samples as usize,
-                                (*data).input_parameter_changes as *mut c_void,
-                                (*data).output_parameter_changes as *mut c_void,
-                                (*data).input_events as *mut c_void,
-                                (*data).output_events as *mut c_void,
-                            );
-
-                            audio_processor.process(&mut process_data);
+                            let mut process_data = ProcessData::<f32>::from_raw(&mut *data);
+
+                            with_sub_block_points(&mut process_data, sub_block_points, |data| {
+                                if !skip_silent_block(data, silent_samples, tail_samples) {
+                                    audio_processor.process(data);
+                                }
+                            });
 
                             *ret.lock().unwrap() = ResOk.into();
                         }
+                        SymbolicSampleSize::Sample64 if audio_processor.process_f64_as_f32() => {
+                            let mut sample_converter = sample_converter.lock().unwrap();
+                            let processed = match sample_converter.as_mut() {
+                                Some(converter) => converter.process(&*data, |process_data| {
+                                    with_sub_block_points(process_data, sub_block_points, |data| {
+                                        if !skip_silent_block(data, silent_samples, tail_samples) {
+                                            audio_processor.process(data);
+                                        }
+                                    })
+                                }),
+                                None => false,
+                            };
+
+                            *ret.lock().unwrap() = if processed {
+                                ResOk.into()
+                            } else {
+                                InvalidArgument.into()
+                            };
+                        }
                         SymbolicSampleSize::Sample64 => {
-                            let mut process_data = ProcessData::<f64>::from_raw(
-                                (*data).num_inputs as usize,
-                                (*data).num_outputs as usize,
-                                (*data).inputs as *const AudioBusBuffers,
-                                (*data).outputs,
-                                ProcessMode::from((*data).process_mode),
-                                (*data).num_samples as usize,
-                                (*data).input_parameter_changes as *mut c_void,
-                                (*data).output_parameter_changes as *mut c_void,
-                                (*data).input_events as *mut c_void,
-                                (*data).output_events as *mut c_void,
-                            );
-
-                            audio_processor.process_f64(&mut process_data);
+                            let mut process_data = ProcessData::<f64>::from_raw(&mut *data);
+
+                            with_sub_block_points(&mut process_data, sub_block_points, |data| {
+                                if !skip_silent_block(data, silent_samples, tail_samples) {
+                                    audio_processor.process_f64(data);
+                                }
+                            });
 
                             *ret.lock().unwrap() = ResOk.into();
                         }
@@ -0,0 +1,74 @@
+use std::ffi::c_void;
+use std::path::PathBuf;
+
+/// Path of the plug-in binary, found from the address of this function.
+pub fn get_module_path() -> Option<PathBuf> {
+    module_path(get_module_path as *const c_void)
+}
+
+/// The `Contents/Resources` folder of the `.vst3` bundle. The binary sits in a folder for its
+/// architecture, e.g. `Contents/x86_64-linux` or `Contents/MacOS`, next to it.
+pub fn get_resources_path() -> Option<PathBuf> {
+    let contents = get_module_path()?.parent()?.parent()?.to_path_buf();
+    Some(contents.join("Resources"))
+}
+
+/// Path of a file in the bundle resources, e.g. `get_resource_path("drums.json")`.
+pub fn get_resource_path(name: &str) -> Option<PathBuf> {
+    Some(get_resources_path()?.join(name))
+}
+
+#[cfg(unix)]
+fn module_path(address: *const c_void) -> Option<PathBuf> {
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int};
+    use std::os::unix::ffi::OsStrExt;
+
+    #[repr(C)]
+    struct DlInfo {
+        dli_fname: *const c_char,
+        dli_fbase: *mut c_void,
+        dli_sname: *const c_char,
+        dli_saddr: *mut c_void,
+    }
+
+    #[cfg_attr(target_os = "linux", link(name = "dl"))]
+    extern "C" {
+        fn dladdr(address: *const c_void, info: *mut DlInfo) -> c_int;
+    }
+
+    unsafe {
+        let mut info: DlInfo = std::mem::zeroed();
+        if dladdr(address, &mut info) == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+        let path = CStr::from_ptr(info.dli_fname).to_bytes();
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(path)))
+    }
+}
+
+#[cfg(windows)]
+fn module_path(address: *const c_void) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::ptr::null_mut;
+    use winapi::um::libloaderapi::{
+        GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+        GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+    };
+
+    unsafe {
+        let mut module = null_mut();
+        let flags =
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT;
+        if GetModuleHandleExW(flags, address as *const u16, &mut module) == 0 {
+            return None;
+        }
+        let mut buffer = vec![0u16; 1024];
+        let len = GetModuleFileNameW(module, buffer.as_mut_ptr(), buffer.len() as u32) as usize;
+        if len == 0 || len >= buffer.len() {
+            return None;
+        }
+        Some(PathBuf::from(OsString::from_wide(&buffer[..len])))
+    }
+}
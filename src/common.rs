@@ -307,6 +307,10 @@ impl UID {
 pub(crate) struct Offset0;
 pub(crate) struct Offset1;
 pub(crate) struct Offset2;
+pub(crate) struct Offset3;
+pub(crate) struct Offset4;
+pub(crate) struct Offset5;
+pub(crate) struct Offset6;
 
 impl Offset for Offset0 {
     const VALUE: usize = 0;
@@ -320,6 +324,22 @@ impl Offset for Offset2 {
     const VALUE: usize = 2;
 }
 
+impl Offset for Offset3 {
+    const VALUE: usize = 3;
+}
+
+impl Offset for Offset4 {
+    const VALUE: usize = 4;
+}
+
+impl Offset for Offset5 {
+    const VALUE: usize = 5;
+}
+
+impl Offset for Offset6 {
+    const VALUE: usize = 6;
+}
+
 #[cfg(test)]
 mod tests {
     use crate::UID;
@@ -6,18 +6,22 @@ use vst3_com::ProductionComInterface;
 use vst3_com::IID;
 use vst3_sys::base::IPluginBase;
 use vst3_sys::vst::{
-    AudioBusBuffers, BusDirections, BusTypes, IAudioProcessor, IComponent, IoModes, MediaTypes,
+    AudioBusBuffers, BusDirections, BusTypes, IAudioProcessor, IComponent,
+    IProcessContextRequirements, IoModes, MediaTypes,
 };
 use vst3_sys::VST3;
 
+use crate::sample_converter::{bus_channels, SampleConverter};
+use crate::sub_block::{SubBlockPoint, SUB_BLOCK_POINTS_CAPACITY};
 use crate::ResultErr::{InternalError, InvalidArgument, NotImplemented, ResultFalse};
 use crate::ResultOk::ResOk;
 use crate::{
     register_panic_msg, wstrcpy, AudioProcessor, ClassInfo, HostApplication, Offset0, Offset1,
-    PluginBase, ProcessData, ProcessMode, ProcessSetup, ResultErr, ResultOk, Stream,
+    Offset2, PluginBase, ProcessData, ProcessMode, ProcessSetup, ResultErr, ResultOk, Stream,
     SymbolicSampleSize, Unknown, UID,
 };
 use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use vst3_com::offset::Offset;
 
@@ -293,8 +297,13 @@ impl Component for DummyComponent {
 pub(crate) struct VST3Component {
     __icomponentvptr: *const <dyn IComponent as vst3_com::ComInterface>::VTable,
     __iaudioprocessorvptr: *const <dyn IAudioProcessor as vst3_com::ComInterface>::VTable,
+    __iprocesscontextrequirementsvptr:
+        *const <dyn IProcessContextRequirements as vst3_com::ComInterface>::VTable,
     __refcnt: std::cell::Cell<u32>,
     inner: Mutex<Box<dyn PluginBase>>,
+    silent_samples: AtomicUsize,
+    sample_converter: Mutex<Option<SampleConverter>>,
+    sub_block_points: Mutex<Vec<SubBlockPoint>>,
 }
 
 impl VST3Component {
@@ -307,11 +316,21 @@ impl VST3Component {
             VST3Component,
         >>::vtable::<Offset1>();
         let __iaudioprocessorvptr = Box::into_raw(Box::new(iaudioprocessor_vtable));
+        let iprocesscontextrequirements_vtable =
+            <dyn IProcessContextRequirements as ::vst3_com::ProductionComInterface<
+                VST3Component,
+            >>::vtable::<Offset2>();
+        let __iprocesscontextrequirementsvptr =
+            Box::into_raw(Box::new(iprocesscontextrequirements_vtable));
         let out = VST3Component {
             __icomponentvptr,
             __iaudioprocessorvptr,
+            __iprocesscontextrequirementsvptr,
             __refcnt: std::cell::Cell::new(1),
             inner,
+            silent_samples: AtomicUsize::new(0),
+            sample_converter: Mutex::new(None),
+            sub_block_points: Mutex::new(Vec::with_capacity(SUB_BLOCK_POINTS_CAPACITY)),
         };
         Box::new(out)
     }
@@ -333,6 +352,9 @@ impl vst3_com::interfaces::IUnknown for VST3Component {
         } else if <dyn IAudioProcessor as vst3_com::ComInterface>::is_iid_in_inheritance_chain(riid)
         {
             *ppv = &self.__iaudioprocessorvptr as *const _ as *mut std::ffi::c_void;
+        } else if <dyn IProcessContextRequirements as vst3_com::ComInterface>::is_iid_in_inheritance_chain(riid)
+        {
+            *ppv = &self.__iprocesscontextrequirementsvptr as *const _ as *mut std::ffi::c_void;
         } else {
             *ppv = std::ptr::null_mut::<std::ffi::c_void>();
             return vst3_com::sys::E_NOINTERFACE;
@@ -367,6 +389,10 @@ impl vst3_com::interfaces::IUnknown for VST3Component {
                 self.__iaudioprocessorvptr
                     as *mut <dyn IAudioProcessor as vst3_com::ComInterface>::VTable,
             );
+            Box::from_raw(
+                self.__iprocesscontextrequirementsvptr
+                    as *mut <dyn IProcessContextRequirements as vst3_com::ComInterface>::VTable,
+            );
             Box::from_raw(self as *const _ as *mut VST3Component);
         }
         __refcnt
@@ -385,6 +411,21 @@ impl VST3Component {
     pub(crate) fn get_plugin_base(&self) -> &Mutex<Box<dyn PluginBase>> {
         &self.inner
     }
+
+    /// Samples since the inputs last had signal, for `AudioProcessor::skip_silence`.
+    pub(crate) fn get_silent_samples(&self) -> &AtomicUsize {
+        &self.silent_samples
+    }
+
+    /// Scratch buffers for `AudioProcessor::process_f64_as_f32`, set in `setup_processing`.
+    pub(crate) fn get_sample_converter(&self) -> &Mutex<Option<SampleConverter>> {
+        &self.sample_converter
+    }
+
+    /// Buffer lent to `ProcessData::sub_blocks`, so that it does not allocate.
+    pub(crate) fn get_sub_block_points(&self) -> &Mutex<Vec<SubBlockPoint>> {
+        &self.sub_block_points
+    }
 }
 
 impl IPluginBase for VST3Component {
@@ -632,11 +673,22 @@ impl IComponent for VST3Component {
 
     unsafe fn set_active(&self, state: u8) -> i32 {
         let mutex_plugin_base = self.get_plugin_base();
+        // A silent stretch does not carry over to the next processing run.
+        self.get_silent_samples().store(0, Ordering::Relaxed);
+        let sample_converter = self.get_sample_converter();
         let ret: Mutex<i32> = Mutex::new(InternalError.into());
         match std::panic::catch_unwind(|| {
             if let Ok(mut plugin_base) = mutex_plugin_base.lock() {
                 if let Some(component) = plugin_base.as_component() {
                     let state = if state != 0 { true } else { false };
+                    if state {
+                        if let Some(converter) = sample_converter.lock().unwrap().as_mut() {
+                            converter.set_channels(
+                                &bus_channels(&*component, BusDirection::Input),
+                                &bus_channels(&*component, BusDirection::Output),
+                            );
+                        }
+                    }
                     return if component.set_active(state) {
                         *ret.lock().unwrap() = ResOk.into()
                     } else {
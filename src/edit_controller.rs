@@ -5,21 +5,42 @@ use std::slice;
 use std::sync::Mutex;
 use widestring::{U16CStr, U16CString};
 
-use vst3_com::ComPtr;
+use vst3_com::interfaces::IUnknown;
+use vst3_com::{ComInterface, ComPtr};
 use vst3_sys::base::IPluginBase;
-use vst3_sys::vst::{IComponentHandler, IEditController, IMidiMapping, IUnitInfo};
+use vst3_sys::vst::{
+    IComponentHandler, IEditController, IKeyswitchController, IMidiLearn, IMidiMapping,
+    INoteExpressionController, IProgramListData, IUnitInfo,
+};
 use vst3_sys::VST3;
 
+use crate::midi_mapping::{read_midi_mapping, write_midi_mapping};
 use crate::plug_view::{PlugView, VST3PlugView};
 use crate::unknown::ResultErr::ResultFalse;
 use crate::unknown::{ResultErr, Unknown};
+use crate::RestartFlag::{MidiCcAssignmentChanged, ParamValuesChanged};
 use crate::ResultErr::{InternalError, InvalidArgument, NotImplemented};
 use crate::ResultOk::ResOk;
 use crate::{
-    wstrcpy, ClassInfo, ClassInfoBuilder, HostApplication, Offset0, Offset1, Offset2,
-    ParameterInfo, PluginBase, ResultOk, Stream, UnitInfo, UID,
+    wstrcpy, ClassInfo, ClassInfoBuilder, EditGesture, EditGroup, HostApplication,
+    MidiMappingTable, Offset0, Offset1, Offset2, Offset3, Offset4, Offset5, Offset6, ParamId,
+    ParameterContainer, ParameterInfo, PluginBase, ResultOk, Stream, UnitInfo, UID,
 };
 
+pub enum RestartFlag {
+    ReloadComponent = vst3_sys::vst::RestartFlags::kReloadComponent as isize,
+    IoChanged = vst3_sys::vst::RestartFlags::kIoChanged as isize,
+    ParamValuesChanged = vst3_sys::vst::RestartFlags::kParamValuesChanged as isize,
+    LatencyChanged = vst3_sys::vst::RestartFlags::kLatencyChanged as isize,
+    ParamTitlesChanged = vst3_sys::vst::RestartFlags::kParamTitlesChanged as isize,
+    MidiCcAssignmentChanged = vst3_sys::vst::RestartFlags::kMidiCCAssignmentChanged as isize,
+    NoteExpressionChanged = vst3_sys::vst::RestartFlags::kNoteExpressionChanged as isize,
+    IoTitlesChanged = vst3_sys::vst::RestartFlags::kIoTitlesChanged as isize,
+    PrefetchableSupportChanged = vst3_sys::vst::RestartFlags::kPrefetchableSupportChanged as isize,
+    RoutingInfoChanged = vst3_sys::vst::RestartFlags::kRoutingInfoChanged as isize,
+}
+
+/// Holds a reference to the host's handler, taken in `from_raw` and in every clone.
 pub struct ComponentHandler {
     inner: ComPtr<dyn IComponentHandler>,
 }
@@ -34,33 +55,53 @@ impl Unknown for ComponentHandler {
         let ptr = ptr as *mut *mut _;
         unsafe {
             let ptr: ComPtr<dyn IComponentHandler> = ComPtr::new(ptr);
+            ptr.add_ref();
             Some(Box::new(Self { inner: ptr }))
         }
     }
 }
 
+impl Clone for ComponentHandler {
+    fn clone(&self) -> Self {
+        unsafe {
+            self.inner.add_ref();
+        }
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Drop for ComponentHandler {
+    fn drop(&mut self) {
+        unsafe {
+            self.inner.release();
+        }
+    }
+}
+
 impl ComponentHandler {
-    pub fn begin_edit(&self, id: u32) -> Result<ResultOk, ResultErr> {
+    pub fn begin_edit(&self, id: ParamId) -> Result<ResultOk, ResultErr> {
         unsafe {
-            match self.inner.begin_edit(id) {
+            match self.inner.begin_edit(id.into()) {
                 r if r == ResOk.into() => Ok(ResOk),
                 r => Err(ResultErr::from(r)),
             }
         }
     }
 
-    pub fn perform_edit(&self, id: u32, value_normalized: f64) -> Result<ResultOk, ResultErr> {
+    pub fn perform_edit(&self, id: ParamId, value_normalized: f64) -> Result<ResultOk, ResultErr> {
         unsafe {
-            match self.inner.perform_edit(id, value_normalized) {
+            match self.inner.perform_edit(id.into(), value_normalized) {
                 r if r == ResOk.into() => Ok(ResOk),
                 r => Err(ResultErr::from(r)),
             }
         }
     }
 
-    pub fn end_edit(&self, id: u32) -> Result<ResultOk, ResultErr> {
+    pub fn end_edit(&self, id: ParamId) -> Result<ResultOk, ResultErr> {
         unsafe {
-            match self.inner.end_edit(id) {
+            match self.inner.end_edit(id.into()) {
                 r if r == ResOk.into() => Ok(ResOk),
                 r => Err(ResultErr::from(r)),
             }
@@ -78,18 +119,148 @@ impl ComponentHandler {
 }
 
 pub trait EditController: PluginBase {
-    fn set_component_state(&mut self, state: &Stream) -> Result<ResultOk, ResultErr>;
-    fn set_state(&mut self, state: &Stream) -> Result<ResultOk, ResultErr>;
-    fn get_state(&self, state: &Stream) -> Result<ResultOk, ResultErr>;
-    fn get_parameter_count(&self) -> Result<usize, ResultErr>;
-    fn get_parameter_info(&self, index: usize) -> Result<&ParameterInfo, ResultErr>;
-    fn get_param_string_by_value(&self, id: usize, value: f64) -> Result<String, ResultErr>;
-    fn get_param_value_by_string(&self, id: usize, string: &str) -> Result<f64, ResultErr>;
-    fn normalized_param_to_plain(&self, id: usize, value: f64) -> Result<f64, ResultErr>;
-    fn plain_param_to_normalized(&self, id: usize, value: f64) -> Result<f64, ResultErr>;
-    fn get_param_normalized(&self, id: usize) -> Result<f64, ResultErr>;
-    fn set_param_normalized(&mut self, id: usize, value: f64) -> Result<ResultOk, ResultErr>;
-    fn set_component_handler(&self, handler: ComponentHandler) -> Result<ResultOk, ResultErr>;
+    fn get_parameters(&self) -> Option<&ParameterContainer> {
+        None
+    }
+
+    fn get_parameters_mut(&mut self) -> Option<&mut ParameterContainer> {
+        None
+    }
+
+    /// The default implementation ignores the component state: parameter values are restored from
+    /// the controller's own state in `set_state`.
+    fn set_component_state(&mut self, _state: &Stream) -> Result<ResultOk, ResultErr> {
+        Ok(ResOk)
+    }
+
+    fn set_state(&mut self, state: &Stream) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        if parameters.set_state(state) {
+            Ok(ResOk)
+        } else {
+            Err(ResultFalse)
+        }
+    }
+
+    fn get_state(&self, state: &Stream) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        if parameters.get_state(state) {
+            Ok(ResOk)
+        } else {
+            Err(ResultFalse)
+        }
+    }
+
+    fn get_parameter_count(&self) -> Result<usize, ResultErr> {
+        match self.get_parameters() {
+            Some(parameters) => Ok(parameters.get_parameter_count()),
+            None => Err(NotImplemented),
+        }
+    }
+
+    fn get_parameter_info(&self, index: usize) -> Result<&ParameterInfo, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        match parameters.get_parameter_by_index(index) {
+            Some(param) => Ok(param.get_info()),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn get_param_string_by_value(&self, id: ParamId, value: f64) -> Result<String, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        match parameters.get_parameter(id) {
+            Some(param) => Ok(param.to_string(value)),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn get_param_value_by_string(&self, id: ParamId, string: &str) -> Result<f64, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        match parameters.get_parameter(id) {
+            Some(param) => param.from_string(string),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn normalized_param_to_plain(&self, id: ParamId, value: f64) -> Result<f64, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        match parameters.get_parameter(id) {
+            Some(param) => Ok(param.to_plain(value)),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn plain_param_to_normalized(&self, id: ParamId, value: f64) -> Result<f64, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        match parameters.get_parameter(id) {
+            Some(param) => Ok(param.to_normalized(value)),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn get_param_normalized(&self, id: ParamId) -> Result<f64, ResultErr> {
+        let parameters = self.get_parameters().ok_or(NotImplemented)?;
+        match parameters.get_parameter(id) {
+            Some(param) => Ok(param.get_normalized()),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn set_param_normalized(&mut self, id: ParamId, value: f64) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        match parameters.get_parameter_mut(id) {
+            Some(param) => {
+                param.set_normalized(value);
+                Ok(ResOk)
+            }
+            None => Err(ResultFalse),
+        }
+    }
+
+    /// `None` when the host releases its handler, e.g. before terminating the controller.
+    fn set_component_handler(
+        &mut self,
+        handler: Option<ComponentHandler>,
+    ) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        parameters.set_component_handler(handler);
+        Ok(ResOk)
+    }
+
+    fn get_component_handler(&self) -> Option<&ComponentHandler> {
+        self.get_parameters()?.get_component_handler()
+    }
+
+    /// Starts an edit of the parameter `id` that lasts until `end_edit`, e.g. on mouse down. The
+    /// edits in between are recorded as one automation gesture and one undo step.
+    fn begin_edit(&mut self, id: ParamId) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        parameters.begin_edit(id)
+    }
+
+    fn perform_edit(&mut self, id: ParamId, value: f64) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        parameters.perform_edit(id, value)
+    }
+
+    fn end_edit(&mut self, id: ParamId) -> Result<ResultOk, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        parameters.end_edit(id)
+    }
+
+    /// Starts an edit of the parameter `id` that ends when the returned guard is dropped. For
+    /// edits made within one call only, see `begin_edit` for gestures.
+    fn edit_gesture(&mut self, id: ParamId) -> Result<EditGesture<'_>, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        EditGesture::new(parameters, id)
+    }
+
+    /// Edits made through the returned group are undone and redone together.
+    fn edit_group(&mut self) -> Result<EditGroup<'_>, ResultErr> {
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        Ok(EditGroup::new(parameters))
+    }
+
     fn create_view(&mut self) -> Option<&mut Box<dyn PlugView>>;
 }
 
@@ -134,33 +305,36 @@ impl EditController for DummyEditController {
 
     fn get_param_string_by_value(
         &self,
-        _id: usize,
+        _id: ParamId,
         _value_normalized: f64,
     ) -> Result<String, ResultErr> {
         unimplemented!()
     }
 
-    fn get_param_value_by_string(&self, _id: usize, _string: &str) -> Result<f64, ResultErr> {
+    fn get_param_value_by_string(&self, _id: ParamId, _string: &str) -> Result<f64, ResultErr> {
         unimplemented!()
     }
 
-    fn normalized_param_to_plain(&self, _id: usize, _value: f64) -> Result<f64, ResultErr> {
+    fn normalized_param_to_plain(&self, _id: ParamId, _value: f64) -> Result<f64, ResultErr> {
         unimplemented!()
     }
 
-    fn plain_param_to_normalized(&self, _id: usize, _plain: f64) -> Result<f64, ResultErr> {
+    fn plain_param_to_normalized(&self, _id: ParamId, _plain: f64) -> Result<f64, ResultErr> {
         unimplemented!()
     }
 
-    fn get_param_normalized(&self, _id: usize) -> Result<f64, ResultErr> {
+    fn get_param_normalized(&self, _id: ParamId) -> Result<f64, ResultErr> {
         unimplemented!()
     }
 
-    fn set_param_normalized(&mut self, _id: usize, _value: f64) -> Result<ResultOk, ResultErr> {
+    fn set_param_normalized(&mut self, _id: ParamId, _value: f64) -> Result<ResultOk, ResultErr> {
         unimplemented!()
     }
 
-    fn set_component_handler(&self, _handler: ComponentHandler) -> Result<ResultOk, ResultErr> {
+    fn set_component_handler(
+        &mut self,
+        _handler: Option<ComponentHandler>,
+    ) -> Result<ResultOk, ResultErr> {
         unimplemented!()
     }
 
@@ -174,6 +348,11 @@ pub(crate) struct VST3EditController {
     __ieditcontrollervptr: *const <dyn IEditController as vst3_com::ComInterface>::VTable,
     __iunitinfovptr: *const <dyn IUnitInfo as vst3_com::ComInterface>::VTable,
     __imidimappingvptr: *const <dyn IMidiMapping as vst3_com::ComInterface>::VTable,
+    __iprogramlistdatavptr: *const <dyn IProgramListData as vst3_com::ComInterface>::VTable,
+    __inoteexpressioncontrollervptr:
+        *const <dyn INoteExpressionController as vst3_com::ComInterface>::VTable,
+    __imidilearnvptr: *const <dyn IMidiLearn as vst3_com::ComInterface>::VTable,
+    __ikeyswitchcontrollervptr: *const <dyn IKeyswitchController as vst3_com::ComInterface>::VTable,
     __refcnt: std::cell::Cell<u32>,
     inner: Mutex<Box<dyn PluginBase>>,
 }
@@ -192,10 +371,33 @@ impl VST3EditController {
             VST3EditController,
         >>::vtable::<Offset2>();
         let __imidimappingvptr = Box::into_raw(Box::new(imidimapping_vtable));
+        let iprogramlistdata_vtable = <dyn IProgramListData as ::vst3_com::ProductionComInterface<
+            VST3EditController,
+        >>::vtable::<Offset3>();
+        let __iprogramlistdatavptr = Box::into_raw(Box::new(iprogramlistdata_vtable));
+        let inoteexpressioncontroller_vtable =
+            <dyn INoteExpressionController as ::vst3_com::ProductionComInterface<
+                VST3EditController,
+            >>::vtable::<Offset4>();
+        let __inoteexpressioncontrollervptr =
+            Box::into_raw(Box::new(inoteexpressioncontroller_vtable));
+        let imidilearn_vtable = <dyn IMidiLearn as ::vst3_com::ProductionComInterface<
+            VST3EditController,
+        >>::vtable::<Offset5>();
+        let __imidilearnvptr = Box::into_raw(Box::new(imidilearn_vtable));
+        let ikeyswitchcontroller_vtable =
+            <dyn IKeyswitchController as ::vst3_com::ProductionComInterface<
+                VST3EditController,
+            >>::vtable::<Offset6>();
+        let __ikeyswitchcontrollervptr = Box::into_raw(Box::new(ikeyswitchcontroller_vtable));
         let out = VST3EditController {
             __ieditcontrollervptr,
             __iunitinfovptr,
             __imidimappingvptr,
+            __iprogramlistdatavptr,
+            __inoteexpressioncontrollervptr,
+            __imidilearnvptr,
+            __ikeyswitchcontrollervptr,
             __refcnt: std::cell::Cell::new(1),
             inner,
         };
@@ -221,6 +423,19 @@ impl vst3_com::interfaces::IUnknown for VST3EditController {
             *ppv = &self.__iunitinfovptr as *const _ as *mut std::ffi::c_void;
         } else if <dyn IMidiMapping as vst3_com::ComInterface>::is_iid_in_inheritance_chain(riid) {
             *ppv = &self.__imidimappingvptr as *const _ as *mut std::ffi::c_void;
+        } else if <dyn IProgramListData as vst3_com::ComInterface>::is_iid_in_inheritance_chain(
+            riid,
+        ) {
+            *ppv = &self.__iprogramlistdatavptr as *const _ as *mut std::ffi::c_void;
+        } else if <dyn INoteExpressionController as ComInterface>::is_iid_in_inheritance_chain(riid)
+        {
+            *ppv = &self.__inoteexpressioncontrollervptr as *const _ as *mut std::ffi::c_void;
+        } else if <dyn IMidiLearn as vst3_com::ComInterface>::is_iid_in_inheritance_chain(riid) {
+            *ppv = &self.__imidilearnvptr as *const _ as *mut std::ffi::c_void;
+        } else if <dyn IKeyswitchController as vst3_com::ComInterface>::is_iid_in_inheritance_chain(
+            riid,
+        ) {
+            *ppv = &self.__ikeyswitchcontrollervptr as *const _ as *mut std::ffi::c_void;
         } else {
             *ppv = std::ptr::null_mut::<std::ffi::c_void>();
             return vst3_com::sys::E_NOINTERFACE;
@@ -259,6 +474,21 @@ impl vst3_com::interfaces::IUnknown for VST3EditController {
                 self.__imidimappingvptr
                     as *mut <dyn IMidiMapping as vst3_com::ComInterface>::VTable,
             );
+            Box::from_raw(
+                self.__iprogramlistdatavptr
+                    as *mut <dyn IProgramListData as vst3_com::ComInterface>::VTable,
+            );
+            Box::from_raw(
+                self.__inoteexpressioncontrollervptr
+                    as *mut <dyn INoteExpressionController as vst3_com::ComInterface>::VTable,
+            );
+            Box::from_raw(
+                self.__imidilearnvptr as *mut <dyn IMidiLearn as vst3_com::ComInterface>::VTable,
+            );
+            Box::from_raw(
+                self.__ikeyswitchcontrollervptr
+                    as *mut <dyn IKeyswitchController as vst3_com::ComInterface>::VTable,
+            );
             Box::from_raw(self as *const _ as *mut VST3EditController);
         }
         __refcnt
@@ -309,6 +539,12 @@ impl IPluginBase for VST3EditController {
         let ret: Mutex<i32> = Mutex::new(InternalError.into());
         match std::panic::catch_unwind(|| {
             if let Ok(mut plugin_base) = mutex_plugin_base.lock() {
+                // The handler must not be called after terminate.
+                if let Some(edit_controller) = plugin_base.as_edit_controller() {
+                    if let Some(parameters) = edit_controller.get_parameters_mut() {
+                        parameters.set_component_handler(None);
+                    }
+                }
                 return if plugin_base.terminate() {
                     *ret.lock().unwrap() = ResOk.into()
                 } else {
@@ -341,9 +577,23 @@ impl IEditController for VST3EditController {
     }
 
     unsafe fn set_state(&self, state: *mut c_void) -> i32 {
-        if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
+        let mut plugin_base = self.get_plugin_base().lock().unwrap();
+        if let Some(edit_controller) = plugin_base.as_edit_controller() {
             if let Some(state) = Stream::from_raw(state) {
-                return match edit_controller.set_state(&*state) {
+                let result = edit_controller.set_state(&*state);
+                if result.is_ok() && read_midi_mapping(&mut **plugin_base, &state) {
+                    let handler = plugin_base
+                        .as_edit_controller()
+                        .and_then(|edit_controller| {
+                            edit_controller.get_component_handler().cloned()
+                        });
+                    // The host may call back into the controller while handling the restart.
+                    drop(plugin_base);
+                    if let Some(handler) = handler {
+                        let _ = handler.restart_component(MidiCcAssignmentChanged as i32);
+                    }
+                }
+                return match result {
                     Ok(r) => r.into(),
                     Err(r) => r.into(),
                 };
@@ -354,9 +604,14 @@ impl IEditController for VST3EditController {
     }
 
     unsafe fn get_state(&self, state: *mut c_void) -> i32 {
-        if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
+        let mut plugin_base = self.get_plugin_base().lock().unwrap();
+        if let Some(edit_controller) = plugin_base.as_edit_controller() {
             if let Some(state) = Stream::from_raw(state) {
-                return match edit_controller.get_state(&*state) {
+                let result = edit_controller.get_state(&*state);
+                if result.is_ok() && !write_midi_mapping(&mut **plugin_base, &state) {
+                    return ResultFalse.into();
+                }
+                return match result {
                     Ok(r) => r.into(),
                     Err(r) => r.into(),
                 };
@@ -413,7 +668,7 @@ impl IEditController for VST3EditController {
         string: *mut i16,
     ) -> i32 {
         if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
-            return match edit_controller.get_param_string_by_value(id as usize, value_normalized) {
+            return match edit_controller.get_param_string_by_value(ParamId(id), value_normalized) {
                 Ok(param_string) => {
                     wstrcpy(&param_string, string);
                     ResOk.into()
@@ -435,7 +690,7 @@ impl IEditController for VST3EditController {
                 return InvalidArgument.into();
             }
             let string = U16CStr::from_ptr_str(string as *const u16).to_string_lossy();
-            return match edit_controller.get_param_value_by_string(id as usize, &string) {
+            return match edit_controller.get_param_value_by_string(ParamId(id), &string) {
                 Ok(value) => {
                     *value_normalized = value;
                     ResOk.into()
@@ -448,7 +703,7 @@ impl IEditController for VST3EditController {
 
     unsafe fn normalized_param_to_plain(&self, id: u32, value_normalized: f64) -> f64 {
         if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
-            return match edit_controller.normalized_param_to_plain(id as usize, value_normalized) {
+            return match edit_controller.normalized_param_to_plain(ParamId(id), value_normalized) {
                 Ok(plain) => plain,
                 Err(_) => 0.0,
             };
@@ -458,7 +713,7 @@ impl IEditController for VST3EditController {
 
     unsafe fn plain_param_to_normalized(&self, id: u32, plain_value: f64) -> f64 {
         if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
-            return match edit_controller.plain_param_to_normalized(id as usize, plain_value) {
+            return match edit_controller.plain_param_to_normalized(ParamId(id), plain_value) {
                 Ok(normalized) => normalized,
                 Err(_) => 0.0,
             };
@@ -468,7 +723,7 @@ impl IEditController for VST3EditController {
 
     unsafe fn get_param_normalized(&self, id: u32) -> f64 {
         if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
-            return match edit_controller.get_param_normalized(id as usize) {
+            return match edit_controller.get_param_normalized(ParamId(id)) {
                 Ok(param_normalized) => param_normalized,
                 Err(_) => 0.0,
             };
@@ -477,24 +732,42 @@ impl IEditController for VST3EditController {
     }
 
     unsafe fn set_param_normalized(&self, id: u32, value: f64) -> i32 {
-        if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
-            return match edit_controller.set_param_normalized(id as usize, value) {
-                Ok(r) => r.into(),
-                Err(r) => r.into(),
-            };
+        let mut plugin_base = self.get_plugin_base().lock().unwrap();
+        let result = match plugin_base.as_edit_controller() {
+            Some(edit_controller) => edit_controller.set_param_normalized(ParamId(id), value),
+            None => return NotImplemented.into(),
+        };
+        let mut program_changed = false;
+        if result.is_ok() {
+            if let Some(unit_info) = plugin_base.as_unit_info() {
+                program_changed = unit_info
+                    .program_parameter_changed(ParamId(id), value)
+                    .is_ok();
+            }
+        }
+        if program_changed {
+            let handler = plugin_base
+                .as_edit_controller()
+                .and_then(|edit_controller| edit_controller.get_component_handler().cloned());
+            // The host may call back into the controller while handling the restart.
+            drop(plugin_base);
+            if let Some(handler) = handler {
+                let _ = handler.restart_component(ParamValuesChanged as i32);
+            }
+        }
+        match result {
+            Ok(r) => r.into(),
+            Err(r) => r.into(),
         }
-        NotImplemented.into()
     }
 
     unsafe fn set_component_handler(&self, handler: *mut c_void) -> i32 {
         if let Some(edit_controller) = self.get_plugin_base().lock().unwrap().as_edit_controller() {
-            if let Some(handler) = ComponentHandler::from_raw(handler) {
-                return match edit_controller.set_component_handler(*handler) {
-                    Ok(r) => r.into(),
-                    Err(r) => r.into(),
-                };
-            }
-            return InvalidArgument.into();
+            let handler = ComponentHandler::from_raw(handler).map(|handler| *handler);
+            return match edit_controller.set_component_handler(handler) {
+                Ok(r) => r.into(),
+                Err(r) => r.into(),
+            };
         }
         NotImplemented.into()
     }
@@ -524,12 +797,26 @@ impl IEditController for VST3EditController {
 }
 
 pub trait MidiMapping: EditController {
+    /// Returning a table makes the default `get_midi_controller_assignment` use it and saves it
+    /// with the controller state.
+    fn get_midi_mapping_table(&self) -> Option<&MidiMappingTable> {
+        None
+    }
+    fn get_midi_mapping_table_mut(&mut self) -> Option<&mut MidiMappingTable> {
+        None
+    }
+
     fn get_midi_controller_assignment(
         &self,
         bus_index: i32,
         channel: i16,
         midi_controller_number: i16,
-    ) -> Result<u32, ResultErr>;
+    ) -> Result<ParamId, ResultErr> {
+        let table = self.get_midi_mapping_table().ok_or(NotImplemented)?;
+        table
+            .get_assignment(bus_index, channel, midi_controller_number)
+            .ok_or(ResultFalse)
+    }
 }
 
 impl IMidiMapping for VST3EditController {
@@ -547,7 +834,7 @@ impl IMidiMapping for VST3EditController {
                 midi_controller_number,
             ) {
                 Ok(assignment_id) => {
-                    *id = assignment_id;
+                    *id = assignment_id.into();
                     ResOk.into()
                 }
                 Err(r) => r.into(),
@@ -0,0 +1,72 @@
+use crate::{ParamId, ParameterContainer, ResultErr, ResultOk};
+
+/// Edit of a single parameter reported to the host: `begin_edit` is sent when the gesture is
+/// created and `end_edit` when it is dropped. The gesture borrows the parameters, so it only
+/// suits edits made within one call; gestures spanning several editor callbacks, e.g. from mouse
+/// down to mouse up, use `begin_edit`, `perform_edit` and `end_edit` instead.
+pub struct EditGesture<'a> {
+    parameters: &'a mut ParameterContainer,
+    id: ParamId,
+}
+
+impl<'a> EditGesture<'a> {
+    pub fn new(parameters: &'a mut ParameterContainer, id: ParamId) -> Result<Self, ResultErr> {
+        parameters.begin_edit(id)?;
+        Ok(Self { parameters, id })
+    }
+
+    pub fn get_id(&self) -> ParamId {
+        self.id
+    }
+
+    pub fn get_normalized(&self) -> f64 {
+        match self.parameters.get_parameter(self.id) {
+            Some(param) => param.get_normalized(),
+            None => 0.0,
+        }
+    }
+
+    /// Updates the parameter and sends its new value to the host with `perform_edit`.
+    pub fn set(&mut self, value_normalized: f64) -> Result<ResultOk, ResultErr> {
+        self.parameters.perform_edit(self.id, value_normalized)
+    }
+}
+
+impl<'a> Drop for EditGesture<'a> {
+    fn drop(&mut self) {
+        if self.parameters.end_edit(self.id).is_err() {
+            log::trace!("EditGesture::drop(): end_edit() failed for {:?}", self.id);
+        }
+    }
+}
+
+/// Groups the gestures started from it into a single undo step.
+pub struct EditGroup<'a> {
+    parameters: &'a mut ParameterContainer,
+}
+
+impl<'a> EditGroup<'a> {
+    pub fn new(parameters: &'a mut ParameterContainer) -> Self {
+        if let Some(history) = parameters.get_edit_history_mut() {
+            history.begin_step();
+        }
+        Self { parameters }
+    }
+
+    pub fn edit_gesture(&mut self, id: ParamId) -> Result<EditGesture<'_>, ResultErr> {
+        EditGesture::new(self.parameters, id)
+    }
+
+    /// Shorthand for a gesture that sets a single value.
+    pub fn set(&mut self, id: ParamId, value_normalized: f64) -> Result<ResultOk, ResultErr> {
+        self.edit_gesture(id)?.set(value_normalized)
+    }
+}
+
+impl<'a> Drop for EditGroup<'a> {
+    fn drop(&mut self) {
+        if let Some(history) = self.parameters.get_edit_history_mut() {
+            history.end_step();
+        }
+    }
+}
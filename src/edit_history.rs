@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use crate::ParamId;
+
+#[derive(Copy, Clone)]
+pub struct ParamChange {
+    pub id: ParamId,
+    pub old_value: f64,
+    pub new_value: f64,
+}
+
+/// Parameter changes undone and redone together.
+#[derive(Clone)]
+pub struct EditStep {
+    changes: Vec<ParamChange>,
+}
+
+impl EditStep {
+    pub fn get_changes(&self) -> &[ParamChange] {
+        &self.changes
+    }
+}
+
+/// Undo and redo stacks of the edits made with `EditGesture` and `EditGroup`. Every outermost
+/// gesture or group is one step.
+pub struct EditHistory {
+    undo_steps: VecDeque<EditStep>,
+    redo_steps: Vec<EditStep>,
+    open_step: Option<EditStep>,
+    depth: usize,
+    max_steps: usize,
+}
+
+impl EditHistory {
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            undo_steps: VecDeque::new(),
+            redo_steps: vec![],
+            open_step: None,
+            depth: 0,
+            max_steps,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_steps.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_steps.is_empty()
+    }
+
+    pub fn get_undo_count(&self) -> usize {
+        self.undo_steps.len()
+    }
+
+    pub fn get_redo_count(&self) -> usize {
+        self.redo_steps.len()
+    }
+
+    /// Also drops the step of an open gesture: its remaining edits are not recorded.
+    pub fn clear(&mut self) {
+        self.undo_steps.clear();
+        self.redo_steps.clear();
+        self.open_step = None;
+        self.depth = 0;
+    }
+
+    pub(crate) fn begin_step(&mut self) {
+        if self.depth == 0 {
+            self.open_step = Some(EditStep { changes: vec![] });
+        }
+        self.depth += 1;
+    }
+
+    /// Repeated changes of the same parameter in a step are merged into one.
+    pub(crate) fn record(&mut self, id: ParamId, old_value: f64, new_value: f64) {
+        let step = match self.open_step.as_mut() {
+            Some(step) => step,
+            None => return,
+        };
+        match step.changes.iter_mut().find(|change| change.id == id) {
+            Some(change) => change.new_value = new_value,
+            None => step.changes.push(ParamChange {
+                id,
+                old_value,
+                new_value,
+            }),
+        }
+    }
+
+    pub(crate) fn end_step(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+        if let Some(mut step) = self.open_step.take() {
+            step.changes
+                .retain(|change| change.old_value != change.new_value);
+            if step.changes.is_empty() {
+                return;
+            }
+            self.undo_steps.push_back(step);
+            if self.undo_steps.len() > self.max_steps {
+                self.undo_steps.pop_front();
+            }
+            self.redo_steps.clear();
+        }
+    }
+
+    /// The step `undo` would revert. `None` while a gesture is open.
+    pub(crate) fn get_undo_step(&self) -> Option<&EditStep> {
+        if self.depth > 0 {
+            return None;
+        }
+        self.undo_steps.back()
+    }
+
+    pub(crate) fn get_redo_step(&self) -> Option<&EditStep> {
+        if self.depth > 0 {
+            return None;
+        }
+        self.redo_steps.last()
+    }
+
+    /// Moves the last undo step to the redo stack once it has been reverted.
+    pub(crate) fn undo_done(&mut self) {
+        if let Some(step) = self.undo_steps.pop_back() {
+            self.redo_steps.push(step);
+        }
+    }
+
+    pub(crate) fn redo_done(&mut self) {
+        if let Some(step) = self.redo_steps.pop() {
+            self.undo_steps.push_back(step);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BaseParameter, EditGroup, EditHistory, ParamId, ParameterContainer, ParameterInfoBuilder,
+    };
+
+    fn parameters() -> ParameterContainer {
+        let mut parameters = ParameterContainer::new();
+        for id in 0..2 {
+            let info = ParameterInfoBuilder::new("Param", ParamId(id)).build();
+            parameters.add_parameter(BaseParameter::new(info));
+        }
+        parameters.set_edit_history(Some(EditHistory::new(2)));
+        parameters
+    }
+
+    fn value(parameters: &ParameterContainer, id: u32) -> f64 {
+        match parameters.get_parameter(ParamId(id)) {
+            Some(param) => param.get_normalized(),
+            None => -1.0,
+        }
+    }
+
+    #[test]
+    fn test_gesture_is_one_step() {
+        let mut parameters = parameters();
+        assert!(parameters.begin_edit(ParamId(0)).is_ok());
+        assert!(parameters.perform_edit(ParamId(0), 0.25).is_ok());
+        assert!(parameters.perform_edit(ParamId(0), 0.75).is_ok());
+        // Undo is refused while the gesture is open.
+        assert!(parameters.undo().is_err());
+        assert!(parameters.end_edit(ParamId(0)).is_ok());
+
+        let history = parameters.get_edit_history().unwrap();
+        assert_eq!(history.get_undo_count(), 1);
+        assert!(parameters.undo().is_ok());
+        assert_eq!(value(&parameters, 0), 0.0);
+        assert!(parameters.redo().is_ok());
+        assert_eq!(value(&parameters, 0), 0.75);
+        assert!(parameters.redo().is_err());
+    }
+
+    #[test]
+    fn test_max_steps_and_redo_cleared() {
+        let mut parameters = parameters();
+        for value in [0.1, 0.2, 0.3].iter() {
+            let mut group = EditGroup::new(&mut parameters);
+            assert!(group.set(ParamId(0), *value).is_ok());
+            assert!(group.set(ParamId(1), *value).is_ok());
+        }
+        assert_eq!(parameters.get_edit_history().unwrap().get_undo_count(), 2);
+
+        assert!(parameters.undo().is_ok());
+        assert_eq!((value(&parameters, 0), value(&parameters, 1)), (0.2, 0.2));
+        assert_eq!(parameters.get_edit_history().unwrap().get_redo_count(), 1);
+
+        let mut group = EditGroup::new(&mut parameters);
+        assert!(group.set(ParamId(1), 0.9).is_ok());
+        drop(group);
+        let history = parameters.get_edit_history().unwrap();
+        assert_eq!((history.get_undo_count(), history.get_redo_count()), (2, 0));
+    }
+
+    #[test]
+    fn test_clear_during_gesture() {
+        let mut parameters = parameters();
+        assert!(parameters.begin_edit(ParamId(0)).is_ok());
+        assert!(parameters.perform_edit(ParamId(0), 0.5).is_ok());
+        parameters.get_edit_history_mut().unwrap().clear();
+        assert!(parameters.perform_edit(ParamId(0), 0.6).is_ok());
+        assert!(parameters.end_edit(ParamId(0)).is_ok());
+        assert_eq!(parameters.get_edit_history().unwrap().get_undo_count(), 0);
+
+        let mut group = EditGroup::new(&mut parameters);
+        assert!(group.set(ParamId(1), 0.5).is_ok());
+        drop(group);
+        assert_eq!(parameters.get_edit_history().unwrap().get_undo_count(), 1);
+        assert!(parameters.undo().is_ok());
+        assert_eq!((value(&parameters, 0), value(&parameters, 1)), (0.6, 0.0));
+    }
+}
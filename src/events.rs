@@ -1,10 +1,20 @@
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter};
+use std::os::raw::c_void;
+use std::slice;
+
+use vst3_com::ComPtr;
 use vst3_sys::vst::EventTypes::*;
-use vst3_sys::vst::{
-    ChordEvent, DataEvent, Event, EventData, EventTypes, IEventList, LegacyMidiCCOutEvent,
-    NoteExpressionTextEvent, NoteExpressionValueEvent, NoteOffEvent, NoteOnEvent,
-    PolyPressureEvent, ScaleEvent,
-};
+use vst3_sys::vst::{Event as RawEvent, IEventList};
+
+use crate::ResultErr::{InvalidArgument, NotImplemented};
+use crate::ResultOk::ResOk;
+use crate::{ResultErr, ResultOk, Unknown, UID};
 
+const EVENT_IS_LIVE: u16 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum EventType {
     NoteOnEvent,
     NoteOffEvent,
@@ -17,19 +27,25 @@ pub enum EventType {
     LegacyMIDICCOutEvent,
 }
 
-impl From<u16> for EventType {
-    fn from(e_ty: u16) -> Self {
+impl TryFrom<u16> for EventType {
+    type Error = ResultErr;
+
+    fn try_from(e_ty: u16) -> Result<Self, Self::Error> {
         match e_ty {
-            e_ty if e_ty == kNoteOnEvent as u16 => EventType::NoteOnEvent,
-            e_ty if e_ty == kNoteOffEvent as u16 => EventType::NoteOffEvent,
-            e_ty if e_ty == kDataEvent as u16 => EventType::DataEvent,
-            e_ty if e_ty == kPolyPressureEvent as u16 => EventType::PolyPressureEvent,
-            e_ty if e_ty == kNoteExpressionValueEvent as u16 => EventType::NoteExpressionValueEvent,
-            e_ty if e_ty == kNoteExpressionTextEvent as u16 => EventType::NoteExpressionTextEvent,
-            e_ty if e_ty == kChordEvent as u16 => EventType::ChordEvent,
-            e_ty if e_ty == kScaleEvent as u16 => EventType::ScaleEvent,
-            e_ty if e_ty == kLegacyMIDICCOutEvent as u16 => EventType::LegacyMIDICCOutEvent,
-            _ => unreachable!(),
+            e_ty if e_ty == kNoteOnEvent as u16 => Ok(EventType::NoteOnEvent),
+            e_ty if e_ty == kNoteOffEvent as u16 => Ok(EventType::NoteOffEvent),
+            e_ty if e_ty == kDataEvent as u16 => Ok(EventType::DataEvent),
+            e_ty if e_ty == kPolyPressureEvent as u16 => Ok(EventType::PolyPressureEvent),
+            e_ty if e_ty == kNoteExpressionValueEvent as u16 => {
+                Ok(EventType::NoteExpressionValueEvent)
+            }
+            e_ty if e_ty == kNoteExpressionTextEvent as u16 => {
+                Ok(EventType::NoteExpressionTextEvent)
+            }
+            e_ty if e_ty == kChordEvent as u16 => Ok(EventType::ChordEvent),
+            e_ty if e_ty == kScaleEvent as u16 => Ok(EventType::ScaleEvent),
+            e_ty if e_ty == kLegacyMIDICCOutEvent as u16 => Ok(EventType::LegacyMIDICCOutEvent),
+            _ => Err(InvalidArgument),
         }
     }
 }
@@ -50,16 +66,361 @@ impl From<EventType> for u16 {
     }
 }
 
-use crate::ResultOk::ResOk;
-use crate::{ResultErr, Unknown, UID};
-use std::cell::RefCell;
-use std::fmt::{Debug, Formatter};
-use std::os::raw::c_void;
-use std::ptr::null;
-use vst3_com::ComPtr;
+/// Fields shared by every event.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EventHeader {
+    pub bus_index: i32,
+    pub sample_offset: i32,
+    pub ppq_position: f64,
+    pub is_live: bool,
+}
+
+/// The data and text of an event borrow from the event list it was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    NoteOn {
+        header: EventHeader,
+        channel: i16,
+        pitch: i16,
+        tuning: f32,
+        velocity: f32,
+        length: i32,
+        note_id: i32,
+    },
+    NoteOff {
+        header: EventHeader,
+        channel: i16,
+        pitch: i16,
+        velocity: f32,
+        note_id: i32,
+        tuning: f32,
+    },
+    Data {
+        header: EventHeader,
+        type_: u32,
+        bytes: &'a [u8],
+    },
+    PolyPressure {
+        header: EventHeader,
+        channel: i16,
+        pitch: i16,
+        pressure: f32,
+        note_id: i32,
+    },
+    NoteExpressionValue {
+        header: EventHeader,
+        type_id: u32,
+        note_id: i32,
+        value: f64,
+    },
+    NoteExpressionText {
+        header: EventHeader,
+        type_id: u32,
+        note_id: i32,
+        text: &'a [u16],
+    },
+    Chord {
+        header: EventHeader,
+        root: i16,
+        bass_note: i16,
+        mask: i16,
+        text: &'a [u16],
+    },
+    Scale {
+        header: EventHeader,
+        root: i16,
+        mask: i16,
+        text: &'a [u16],
+    },
+    LegacyMidiCcOut {
+        header: EventHeader,
+        control_number: u8,
+        channel: i8,
+        value: i8,
+        value2: i8,
+    },
+}
+
+impl<'a> Event<'a> {
+    pub fn get_header(&self) -> &EventHeader {
+        match self {
+            Event::NoteOn { header, .. }
+            | Event::NoteOff { header, .. }
+            | Event::Data { header, .. }
+            | Event::PolyPressure { header, .. }
+            | Event::NoteExpressionValue { header, .. }
+            | Event::NoteExpressionText { header, .. }
+            | Event::Chord { header, .. }
+            | Event::Scale { header, .. }
+            | Event::LegacyMidiCcOut { header, .. } => header,
+        }
+    }
+
+    pub fn get_type(&self) -> EventType {
+        match self {
+            Event::NoteOn { .. } => EventType::NoteOnEvent,
+            Event::NoteOff { .. } => EventType::NoteOffEvent,
+            Event::Data { .. } => EventType::DataEvent,
+            Event::PolyPressure { .. } => EventType::PolyPressureEvent,
+            Event::NoteExpressionValue { .. } => EventType::NoteExpressionValueEvent,
+            Event::NoteExpressionText { .. } => EventType::NoteExpressionTextEvent,
+            Event::Chord { .. } => EventType::ChordEvent,
+            Event::Scale { .. } => EventType::ScaleEvent,
+            Event::LegacyMidiCcOut { .. } => EventType::LegacyMIDICCOutEvent,
+        }
+    }
+
+    /// The pointers in `event` must stay valid for `'a`.
+    pub(crate) unsafe fn from_raw(event: &RawEvent) -> Result<Self, ResultErr> {
+        let header = EventHeader {
+            bus_index: event.bus_index,
+            sample_offset: event.sample_offset,
+            ppq_position: event.ppq_position,
+            is_live: event.flags & EVENT_IS_LIVE != 0,
+        };
+        let event_type = match EventType::try_from(event.type_) {
+            Ok(event_type) => event_type,
+            Err(r) => {
+                log::trace!("Event::from_raw(): unknown event type {}", event.type_);
+                return Err(r);
+            }
+        };
+        let data = &event.event;
+        Ok(match event_type {
+            EventType::NoteOnEvent => Event::NoteOn {
+                header,
+                channel: data.note_on.channel,
+                pitch: data.note_on.pitch,
+                tuning: data.note_on.tuning,
+                velocity: data.note_on.velocity,
+                length: data.note_on.length,
+                note_id: data.note_on.note_id,
+            },
+            EventType::NoteOffEvent => Event::NoteOff {
+                header,
+                channel: data.note_off.channel,
+                pitch: data.note_off.pitch,
+                velocity: data.note_off.velocity,
+                note_id: data.note_off.note_id,
+                tuning: data.note_off.tuning,
+            },
+            EventType::DataEvent => Event::Data {
+                header,
+                type_: data.data.type_,
+                bytes: raw_slice(data.data.bytes, data.data.size as usize),
+            },
+            EventType::PolyPressureEvent => Event::PolyPressure {
+                header,
+                channel: data.poly_pressure.channel,
+                pitch: data.poly_pressure.pitch,
+                pressure: data.poly_pressure.pressure,
+                note_id: data.poly_pressure.note_id,
+            },
+            EventType::NoteExpressionValueEvent => Event::NoteExpressionValue {
+                header,
+                type_id: data.note_expression_value.type_id,
+                note_id: data.note_expression_value.note_id,
+                value: data.note_expression_value.value,
+            },
+            EventType::NoteExpressionTextEvent => Event::NoteExpressionText {
+                header,
+                type_id: data.note_expression_text.type_id,
+                note_id: data.note_expression_text.note_id,
+                text: raw_slice(
+                    data.note_expression_text.text as *const u16,
+                    data.note_expression_text.text_len as usize,
+                ),
+            },
+            EventType::ChordEvent => Event::Chord {
+                header,
+                root: data.chord.root,
+                bass_note: data.chord.bass_note,
+                mask: data.chord.mask,
+                text: raw_slice(data.chord.text as *const u16, data.chord.text_len as usize),
+            },
+            EventType::ScaleEvent => Event::Scale {
+                header,
+                root: data.scale.root,
+                mask: data.scale.mask,
+                text: raw_slice(data.scale.text as *const u16, data.scale.text_len as usize),
+            },
+            EventType::LegacyMIDICCOutEvent => Event::LegacyMidiCcOut {
+                header,
+                control_number: data.legacy_midi_cc_out.control_number,
+                channel: data.legacy_midi_cc_out.channel,
+                value: data.legacy_midi_cc_out.value,
+                value2: data.legacy_midi_cc_out.value2,
+            },
+        })
+    }
+
+    /// The text and data of the returned event point into `self`.
+    pub(crate) fn to_raw(&self) -> RawEvent {
+        let header = self.get_header();
+        let mut event: RawEvent = unsafe { std::mem::zeroed() };
+        event.bus_index = header.bus_index;
+        event.sample_offset = header.sample_offset;
+        event.ppq_position = header.ppq_position;
+        event.flags = if header.is_live { EVENT_IS_LIVE } else { 0 };
+        event.type_ = self.get_type().into();
+        let data = &mut event.event;
+        match *self {
+            Event::NoteOn {
+                channel,
+                pitch,
+                tuning,
+                velocity,
+                length,
+                note_id,
+                ..
+            } => {
+                data.note_on.channel = channel;
+                data.note_on.pitch = pitch;
+                data.note_on.tuning = tuning;
+                data.note_on.velocity = velocity;
+                data.note_on.length = length;
+                data.note_on.note_id = note_id;
+            }
+            Event::NoteOff {
+                channel,
+                pitch,
+                velocity,
+                note_id,
+                tuning,
+                ..
+            } => {
+                data.note_off.channel = channel;
+                data.note_off.pitch = pitch;
+                data.note_off.velocity = velocity;
+                data.note_off.note_id = note_id;
+                data.note_off.tuning = tuning;
+            }
+            Event::Data { type_, bytes, .. } => {
+                data.data.type_ = type_;
+                data.data.size = bytes.len() as u32;
+                data.data.bytes = bytes.as_ptr();
+            }
+            Event::PolyPressure {
+                channel,
+                pitch,
+                pressure,
+                note_id,
+                ..
+            } => {
+                data.poly_pressure.channel = channel;
+                data.poly_pressure.pitch = pitch;
+                data.poly_pressure.pressure = pressure;
+                data.poly_pressure.note_id = note_id;
+            }
+            Event::NoteExpressionValue {
+                type_id,
+                note_id,
+                value,
+                ..
+            } => {
+                data.note_expression_value.type_id = type_id;
+                data.note_expression_value.note_id = note_id;
+                data.note_expression_value.value = value;
+            }
+            Event::NoteExpressionText {
+                type_id,
+                note_id,
+                text,
+                ..
+            } => {
+                data.note_expression_text.type_id = type_id;
+                data.note_expression_text.note_id = note_id;
+                data.note_expression_text.text_len = text.len() as _;
+                data.note_expression_text.text = text.as_ptr() as _;
+            }
+            Event::Chord {
+                root,
+                bass_note,
+                mask,
+                text,
+                ..
+            } => {
+                data.chord.root = root;
+                data.chord.bass_note = bass_note;
+                data.chord.mask = mask;
+                data.chord.text_len = text.len() as _;
+                data.chord.text = text.as_ptr() as _;
+            }
+            Event::Scale {
+                root, mask, text, ..
+            } => {
+                data.scale.root = root;
+                data.scale.mask = mask;
+                data.scale.text_len = text.len() as _;
+                data.scale.text = text.as_ptr() as _;
+            }
+            Event::LegacyMidiCcOut {
+                control_number,
+                channel,
+                value,
+                value2,
+                ..
+            } => {
+                data.legacy_midi_cc_out.control_number = control_number;
+                data.legacy_midi_cc_out.channel = channel;
+                data.legacy_midi_cc_out.value = value;
+                data.legacy_midi_cc_out.value2 = value2;
+            }
+        }
+        event
+    }
+}
+
+unsafe fn raw_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() || len == 0 {
+        return &[];
+    }
+    slice::from_raw_parts(ptr, len)
+}
 
 pub struct EventList {
-    inner: ComPtr<dyn IEventList>,
+    inner: EventListInner,
+}
+
+enum EventListInner {
+    Host(ComPtr<dyn IEventList>),
+    Owned(RefCell<Vec<OwnedEvent>>),
+}
+
+/// An event whose bytes and text were copied, with the pointers of `raw` redirected to the copies.
+struct OwnedEvent {
+    raw: RawEvent,
+    _bytes: Box<[u8]>,
+    _text: Box<[u16]>,
+}
+
+impl OwnedEvent {
+    fn new(event: &Event) -> Self {
+        let bytes: Box<[u8]> = match *event {
+            Event::Data { bytes, .. } => bytes.into(),
+            _ => Box::new([]),
+        };
+        let text: Box<[u16]> = match *event {
+            Event::NoteExpressionText { text, .. }
+            | Event::Chord { text, .. }
+            | Event::Scale { text, .. } => text.into(),
+            _ => Box::new([]),
+        };
+        let mut raw = event.to_raw();
+        let data = &mut raw.event;
+        match event {
+            Event::Data { .. } => data.data.bytes = bytes.as_ptr(),
+            Event::NoteExpressionText { .. } => data.note_expression_text.text = text.as_ptr() as _,
+            Event::Chord { .. } => data.chord.text = text.as_ptr() as _,
+            Event::Scale { .. } => data.scale.text = text.as_ptr() as _,
+            _ => {}
+        }
+        Self {
+            raw,
+            _bytes: bytes,
+            _text: text,
+        }
+    }
 }
 
 impl Unknown for EventList {
@@ -72,42 +433,131 @@ impl Unknown for EventList {
         let ptr = ptr as *mut *mut _;
         unsafe {
             let ptr: ComPtr<dyn IEventList> = ComPtr::new(ptr);
-            Some(Box::new(Self { inner: ptr }))
+            Some(Box::new(Self {
+                inner: EventListInner::Host(ptr),
+            }))
         }
     }
 }
 
 impl EventList {
+    /// An empty list owned by the plug-in instead of the host, e.g. to call `process` from tests
+    /// through `ProcessDataBuilder`. Added events are copied.
+    pub fn new() -> Self {
+        Self {
+            inner: EventListInner::Owned(RefCell::new(vec![])),
+        }
+    }
+
     pub fn get_event_count(&self) -> i32 {
-        unsafe { self.inner.get_event_count() }
-    }
-
-    pub fn get_event(&self, index: i32) -> Result<Event, ResultErr> {
-        // todo: make safe wrapper of Event
-        let mut event: Event = Event {
-            bus_index: 0,
-            sample_offset: 0,
-            ppq_position: 0.0,
-            flags: 0,
-            type_: 0,
-            event: EventData {
-                note_on: NoteOnEvent {
-                    channel: 0,
-                    pitch: 0,
-                    tuning: 0.0,
-                    velocity: 0,
-                    length: 0,
-                    note_id: 0,
-                },
-            },
-        };
+        match &self.inner {
+            EventListInner::Host(list) => unsafe { list.get_event_count() },
+            EventListInner::Owned(events) => events.borrow().len() as i32,
+        }
+    }
+
+    pub fn get_event(&self, index: i32) -> Result<Event<'_>, ResultErr> {
         unsafe {
-            match self.inner.get_event(index, &mut event as *mut _) {
-                r if r == ResOk.into() => Ok(event),
-                r => Err(ResultErr::from(r)),
+            match &self.inner {
+                EventListInner::Host(list) => {
+                    let mut event: RawEvent = std::mem::zeroed();
+                    match list.get_event(index, &mut event as *mut _) {
+                        r if r == ResOk.into() => Event::from_raw(&event),
+                        r => Err(ResultErr::from(r)),
+                    }
+                }
+                // The copied bytes and text are boxed, so they stay put while `self` lives.
+                EventListInner::Owned(events) => match events.borrow().get(index as usize) {
+                    Some(owned) if index >= 0 => Event::from_raw(&owned.raw),
+                    _ => Err(InvalidArgument),
+                },
             }
         }
     }
+
+    /// Only takes events whose bytes and text live for the whole program, since the host keeps
+    /// pointers to them. See `add_event_unchecked` for other events.
+    pub fn add_event(&self, event: &Event<'static>) -> Result<ResultOk, ResultErr> {
+        unsafe { self.add_event_unchecked(event) }
+    }
+
+    /// # Safety
+    ///
+    /// The host only copies the pointers to the bytes and text of `event`: they must stay valid
+    /// until the end of the process call. Lists created with `new` copy them instead.
+    pub unsafe fn add_event_unchecked(&self, event: &Event) -> Result<ResultOk, ResultErr> {
+        match &self.inner {
+            EventListInner::Host(list) => {
+                let mut event = event.to_raw();
+                match list.add_event(&mut event as *mut _) {
+                    r if r == ResOk.into() => Ok(ResOk),
+                    r => Err(ResultErr::from(r)),
+                }
+            }
+            EventListInner::Owned(_) => self.add_copied_event(event),
+        }
+    }
+
+    /// Copies `event` into a list created with `new`. Host lists return `NotImplemented`, since
+    /// they would keep pointers to the bytes and text instead.
+    pub fn add_copied_event(&self, event: &Event) -> Result<ResultOk, ResultErr> {
+        match &self.inner {
+            EventListInner::Host(_) => Err(NotImplemented),
+            EventListInner::Owned(events) => {
+                events.borrow_mut().push(OwnedEvent::new(event));
+                Ok(ResOk)
+            }
+        }
+    }
+
+    /// Events of unknown types are skipped.
+    pub fn iter(&self) -> EventIter<'_> {
+        EventIter {
+            list: self,
+            index: 0,
+            count: self.get_event_count(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a EventList {
+    type Item = Event<'a>;
+    type IntoIter = EventIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct EventIter<'a> {
+    list: &'a EventList,
+    index: i32,
+    count: i32,
+}
+
+impl<'a> Iterator for EventIter<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.count {
+            let index = self.index;
+            self.index += 1;
+            if let Ok(event) = self.list.get_event(index) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some((self.count - self.index).max(0) as usize))
+    }
+}
+
+impl Default for EventList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Debug for EventList {
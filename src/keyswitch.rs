@@ -0,0 +1,192 @@
+use vst3_sys::vst::IKeyswitchController;
+
+use crate::ResultErr::{InvalidArgument, NotImplemented};
+use crate::ResultOk::ResOk;
+use crate::{wstrcpy, EditController, ResultErr, VST3EditController};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KeyswitchType {
+    /// Switches when the key is pressed.
+    NoteOn,
+    /// Switches while the key is held, for the notes played meanwhile.
+    OnTheFly,
+    /// Switches when the key is released.
+    OnRelease,
+    /// The key range plays the articulation itself.
+    KeyRange,
+}
+
+impl From<KeyswitchType> for u32 {
+    fn from(type_: KeyswitchType) -> Self {
+        match type_ {
+            KeyswitchType::NoteOn => 0,
+            KeyswitchType::OnTheFly => 1,
+            KeyswitchType::OnRelease => 2,
+            KeyswitchType::KeyRange => 3,
+        }
+    }
+}
+
+pub struct KeyswitchInfo {
+    pub type_: KeyswitchType,
+    pub title: String,
+    pub short_title: Option<String>,
+    pub key_min: i32,
+    pub key_max: i32,
+    /// Key shown in the host instead of the range, -1 if none.
+    pub key_remapped: i32,
+    pub unit_id: i32,
+}
+
+impl KeyswitchInfo {
+    pub fn get_info(&self) -> vst3_sys::vst::KeyswitchInfo {
+        let mut info = vst3_sys::vst::KeyswitchInfo {
+            type_id: self.type_.into(),
+            title: [0; 128],
+            short_title: [0; 128],
+            keyswitch_min: self.key_min,
+            keyswitch_max: self.key_max,
+            key_remapped: self.key_remapped,
+            unit_id: self.unit_id,
+            flags: 0,
+        };
+
+        unsafe {
+            wstrcpy(&self.title, info.title.as_mut_ptr());
+            if let Some(short_title) = &self.short_title {
+                wstrcpy(short_title, info.short_title.as_mut_ptr());
+            }
+        }
+
+        info
+    }
+}
+
+pub struct KeyswitchInfoBuilder {
+    type_: KeyswitchType,
+    title: String,
+    short_title: Option<String>,
+    key_min: i32,
+    key_max: i32,
+    key_remapped: i32,
+    unit_id: i32,
+}
+
+impl KeyswitchInfoBuilder {
+    /// Keyswitch on a single key.
+    pub fn new(title: &str, type_: KeyswitchType, key: i32) -> Self {
+        Self {
+            type_,
+            title: title.to_string(),
+            short_title: None,
+            key_min: key,
+            key_max: key,
+            key_remapped: -1,
+            unit_id: crate::ROOT_UNIT_ID,
+        }
+    }
+
+    pub fn short_title(mut self, short_title: &str) -> Self {
+        self.short_title = Some(short_title.to_string());
+        self
+    }
+
+    pub fn key_range(mut self, key_min: i32, key_max: i32) -> Self {
+        self.key_min = key_min;
+        self.key_max = key_max;
+        self
+    }
+
+    pub fn key_remapped(mut self, key: i32) -> Self {
+        self.key_remapped = key;
+        self
+    }
+
+    pub fn unit_id(mut self, unit_id: i32) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    pub fn build(self) -> KeyswitchInfo {
+        KeyswitchInfo {
+            type_: self.type_,
+            title: self.title,
+            short_title: self.short_title,
+            key_min: self.key_min,
+            key_max: self.key_max,
+            key_remapped: self.key_remapped,
+            unit_id: self.unit_id,
+        }
+    }
+}
+
+pub trait KeyswitchController: EditController {
+    /// Keyswitches of a channel of an event input bus.
+    fn get_keyswitches(&self, _bus_index: i32, _channel: i16) -> &[KeyswitchInfo] {
+        &[]
+    }
+
+    fn get_keyswitch_count(&self, bus_index: i32, channel: i16) -> Result<usize, ResultErr> {
+        Ok(self.get_keyswitches(bus_index, channel).len())
+    }
+
+    fn get_keyswitch_info(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        keyswitch_index: usize,
+    ) -> Result<&KeyswitchInfo, ResultErr> {
+        match self
+            .get_keyswitches(bus_index, channel)
+            .get(keyswitch_index)
+        {
+            Some(info) => Ok(info),
+            None => Err(InvalidArgument),
+        }
+    }
+}
+
+impl IKeyswitchController for VST3EditController {
+    unsafe fn get_keyswitch_count(&self, bus_index: i32, channel: i16) -> i32 {
+        if let Some(controller) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_keyswitch_controller()
+        {
+            return match controller.get_keyswitch_count(bus_index, channel) {
+                Ok(count) => count as i32,
+                Err(_) => 0,
+            };
+        }
+        0
+    }
+
+    unsafe fn get_keyswitch_info(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        keyswitch_index: i32,
+        info: *mut vst3_sys::vst::KeyswitchInfo,
+    ) -> i32 {
+        if let Some(controller) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_keyswitch_controller()
+        {
+            if keyswitch_index < 0 || info.is_null() {
+                return InvalidArgument.into();
+            }
+            return match controller.get_keyswitch_info(bus_index, channel, keyswitch_index as usize)
+            {
+                Ok(keyswitch) => {
+                    *info = keyswitch.get_info();
+                    ResOk.into()
+                }
+                Err(r) => r.into(),
+            };
+        }
+        NotImplemented.into()
+    }
+}
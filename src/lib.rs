@@ -1,40 +1,79 @@
+mod audio_buffer;
 mod audio_processor;
+mod bundle;
 mod bus;
 mod common;
 mod component;
 mod edit_controller;
+mod edit_gesture;
+mod edit_history;
 mod events;
 mod factory;
 mod host_application;
+mod keyswitch;
 mod logging;
+mod meter;
+mod midi;
+mod midi_file;
+mod midi_learn;
+mod midi_mapping;
+mod mpe;
+mod musical_time;
+mod note_expression;
 mod parameter_changes;
 mod parameters;
+mod pitch_names;
 mod plug_view;
 mod plugin_base;
+mod process_context;
+mod process_data_builder;
+mod program_list_data;
+mod sample_converter;
 mod speaker;
 mod stream;
+mod sub_block;
 mod unit;
 mod unit_info;
 mod unknown;
+mod voice;
 
+pub use audio_buffer::*;
 pub use audio_processor::*;
+pub use bundle::*;
 pub use bus::*;
 pub use common::*;
 pub use component::*;
 pub use edit_controller::*;
+pub use edit_gesture::*;
+pub use edit_history::*;
 pub use events::*;
 pub use factory::*;
 pub use host_application::*;
+pub use keyswitch::*;
 pub use logging::*;
+pub use meter::*;
+pub use midi::*;
+pub use midi_file::*;
+pub use midi_learn::*;
+pub use midi_mapping::*;
+pub use mpe::*;
+pub use musical_time::*;
+pub use note_expression::*;
 pub use parameter_changes::*;
 pub use parameters::*;
+pub use pitch_names::*;
 pub use plug_view::*;
 pub use plugin_base::*;
+pub use process_context::*;
+pub use process_data_builder::*;
+pub use program_list_data::*;
 pub use speaker::*;
 pub use stream::*;
+pub use sub_block::*;
 pub use unit::*;
 pub use unit_info::*;
 pub use unknown::*;
+pub use voice::*;
 
 use std::os::raw::c_void;
 
@@ -110,6 +149,26 @@ macro_rules! plugin_main {
     };
 }
 
+#[macro_export]
+macro_rules! generic_process {
+    () => {
+        fn can_process_sample_size(
+            &self,
+            _symbolic_sample_size: &$crate::SymbolicSampleSize,
+        ) -> bool {
+            true
+        }
+
+        fn process(&mut self, data: &mut $crate::ProcessData<f32>) {
+            $crate::GenericProcessor::process(self, data)
+        }
+
+        fn process_f64(&mut self, data: &mut $crate::ProcessData<f64>) {
+            $crate::GenericProcessor::process(self, data)
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! factory_main {
     ($t:ty) => {
@@ -0,0 +1,169 @@
+use num_traits::Float;
+
+use crate::ParameterFlag::IsReadOnly;
+use crate::{BaseParameter, OutputAudioBusBuffer, ParamId, ParameterChanges, ParameterInfoBuilder};
+
+pub enum MeterMode {
+    Peak,
+    Rms,
+}
+
+/// Level of an output bus sent to the controller as a read-only parameter.
+pub struct Meter {
+    id: ParamId,
+    mode: MeterMode,
+    attack_time: f64,
+    release_time: f64,
+    update_interval: f64,
+    sample_rate: f64,
+    value: f64,
+    last_written: Option<f64>,
+    samples_since_write: usize,
+}
+
+impl Meter {
+    /// Creates the parameter the controller has to register for a meter with the same `id`.
+    pub fn create_parameter(title: &str, id: ParamId) -> Box<BaseParameter> {
+        let info = ParameterInfoBuilder::new(title, id)
+            .step_count(0)
+            .default_normalized_value(0.0)
+            .flags(IsReadOnly as i32)
+            .build();
+        BaseParameter::new(info)
+    }
+
+    pub fn get_id(&self) -> ParamId {
+        self.id
+    }
+
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.last_written = None;
+        self.samples_since_write = 0;
+    }
+
+    /// Measures all the channels of `buffer`.
+    pub fn process_buffer<T: Float>(&mut self, buffer: &OutputAudioBusBuffer<T>) {
+        let num_samples = buffer.num_samples();
+        let mut peak = 0.0f64;
+        let mut sum = 0.0f64;
+        for channel in buffer.iter() {
+            for sample in channel {
+                let sample = sample.to_f64().unwrap_or(0.0).abs();
+                peak = peak.max(sample);
+                sum += sample * sample;
+            }
+        }
+        let level = match self.mode {
+            MeterMode::Peak => peak,
+            MeterMode::Rms if num_samples > 0 && !buffer.is_empty() => {
+                (sum / (num_samples * buffer.len()) as f64).sqrt()
+            }
+            MeterMode::Rms => 0.0,
+        };
+        self.process_value(level, num_samples);
+    }
+
+    /// Feeds a level computed by the plug-in for a block of `num_samples` samples.
+    pub fn process_value(&mut self, level: f64, num_samples: usize) {
+        let level = level.clamp(0.0, 1.0);
+        let time = if level > self.value {
+            self.attack_time
+        } else {
+            self.release_time
+        };
+        let coefficient = if time > 0.0 && self.sample_rate > 0.0 {
+            (-(num_samples as f64) / (time * self.sample_rate)).exp()
+        } else {
+            0.0
+        };
+        self.value = level + (self.value - level) * coefficient;
+        self.samples_since_write += num_samples;
+    }
+
+    /// Adds the current value to `changes` once per update interval, unless it did not change
+    /// since the last write.
+    pub fn write(&mut self, changes: &ParameterChanges) {
+        let interval = (self.update_interval * self.sample_rate) as usize;
+        if self.last_written.is_some() && self.samples_since_write < interval {
+            return;
+        }
+        if let Some(last_written) = self.last_written {
+            if (last_written - self.value).abs() < 1e-6 {
+                return;
+            }
+        }
+        if let Some((param_queue, _)) = changes.add_parameter_data(self.id) {
+            let mut index = 0;
+            if param_queue.add_point(0, self.value, &mut index).is_ok() {
+                self.last_written = Some(self.value);
+                self.samples_since_write = 0;
+            }
+        }
+    }
+}
+
+pub struct MeterBuilder {
+    id: ParamId,
+    mode: MeterMode,
+    attack_time: f64,
+    release_time: f64,
+    update_interval: f64,
+}
+
+impl MeterBuilder {
+    pub fn new(id: ParamId) -> Self {
+        Self {
+            id,
+            mode: MeterMode::Peak,
+            attack_time: 0.0,
+            release_time: 0.3,
+            update_interval: 1.0 / 30.0,
+        }
+    }
+
+    pub fn mode(mut self, mode: MeterMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Time in seconds to reach about 63% of a rising level.
+    pub fn attack_time(mut self, attack_time: f64) -> Self {
+        self.attack_time = attack_time;
+        self
+    }
+
+    /// Time in seconds to reach about 63% of a falling level.
+    pub fn release_time(mut self, release_time: f64) -> Self {
+        self.release_time = release_time;
+        self
+    }
+
+    /// Number of values written per second.
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.update_interval = if rate > 0.0 { 1.0 / rate } else { 0.0 };
+        self
+    }
+
+    pub fn build(self) -> Meter {
+        Meter {
+            id: self.id,
+            mode: self.mode,
+            attack_time: self.attack_time,
+            release_time: self.release_time,
+            update_interval: self.update_interval,
+            sample_rate: 44100.0,
+            value: 0.0,
+            last_written: None,
+            samples_since_write: 0,
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{Event, EventHeader};
+
+/// Controller numbers of `Event::LegacyMidiCcOut` for the MIDI messages that are not control
+/// changes.
+pub const CTRL_AFTER_TOUCH: u8 = 128;
+pub const CTRL_PITCH_BEND: u8 = 129;
+pub const CTRL_PROGRAM_CHANGE: u8 = 130;
+
+/// Type of the `Event::Data` events holding a system exclusive message.
+pub const DATA_TYPE_MIDI_SYSEX: u32 = 0;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const POLY_PRESSURE: u8 = 0xA0;
+const CONTROL_CHANGE: u8 = 0xB0;
+const PROGRAM_CHANGE: u8 = 0xC0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
+const PITCH_BEND: u8 = 0xE0;
+const SYSEX: u8 = 0xF0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MidiMessage<'a> {
+    Short {
+        sample_offset: i32,
+        bytes: [u8; 3],
+        len: usize,
+    },
+    SysEx {
+        sample_offset: i32,
+        bytes: &'a [u8],
+    },
+}
+
+impl<'a> MidiMessage<'a> {
+    pub fn get_sample_offset(&self) -> i32 {
+        match self {
+            MidiMessage::Short { sample_offset, .. } | MidiMessage::SysEx { sample_offset, .. } => {
+                *sample_offset
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            MidiMessage::Short { bytes, len, .. } => &bytes[..*len],
+            MidiMessage::SysEx { bytes, .. } => bytes,
+        }
+    }
+}
+
+/// Returns `None` for events without a MIDI 1.0 equivalent.
+pub fn event_to_midi<'a>(event: &Event<'a>) -> Option<MidiMessage<'a>> {
+    let sample_offset = event.get_header().sample_offset;
+    let short = |status: u8, channel: i16, data1: u8, data2: u8, len: usize| {
+        Some(MidiMessage::Short {
+            sample_offset,
+            bytes: [status | (channel as u8 & 0x0F), data1 & 0x7F, data2 & 0x7F],
+            len,
+        })
+    };
+    match *event {
+        Event::NoteOn {
+            channel,
+            pitch,
+            velocity,
+            ..
+        } => {
+            // A note on with a velocity of 0 would be read as a note off.
+            let velocity = velocity_to_midi(velocity).max(1);
+            short(NOTE_ON, channel, pitch as u8, velocity, 3)
+        }
+        Event::NoteOff {
+            channel,
+            pitch,
+            velocity,
+            ..
+        } => short(
+            NOTE_OFF,
+            channel,
+            pitch as u8,
+            velocity_to_midi(velocity),
+            3,
+        ),
+        Event::PolyPressure {
+            channel,
+            pitch,
+            pressure,
+            ..
+        } => short(
+            POLY_PRESSURE,
+            channel,
+            pitch as u8,
+            velocity_to_midi(pressure),
+            3,
+        ),
+        Event::LegacyMidiCcOut {
+            control_number,
+            channel,
+            value,
+            value2,
+            ..
+        } => {
+            let channel = channel as i16;
+            match control_number {
+                CTRL_AFTER_TOUCH => short(CHANNEL_PRESSURE, channel, value as u8, 0, 2),
+                CTRL_PITCH_BEND => short(PITCH_BEND, channel, value as u8, value2 as u8, 3),
+                CTRL_PROGRAM_CHANGE => short(PROGRAM_CHANGE, channel, value as u8, 0, 2),
+                cc if cc < 128 => short(CONTROL_CHANGE, channel, cc, value as u8, 3),
+                _ => None,
+            }
+        }
+        Event::Data { type_, bytes, .. } if type_ == DATA_TYPE_MIDI_SYSEX => {
+            Some(MidiMessage::SysEx {
+                sample_offset,
+                bytes,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Converts a single MIDI 1.0 message. Returns `None` for running status, system common and
+/// real time messages.
+pub fn midi_to_event(bus_index: i32, sample_offset: i32, bytes: &[u8]) -> Option<Event<'_>> {
+    let header = EventHeader {
+        bus_index,
+        sample_offset,
+        ppq_position: 0.0,
+        is_live: false,
+    };
+    let status = *bytes.first()?;
+    if status == SYSEX {
+        return Some(Event::Data {
+            header,
+            type_: DATA_TYPE_MIDI_SYSEX,
+            bytes,
+        });
+    }
+    let channel = (status & 0x0F) as i16;
+    let data1 = bytes.get(1).map(|b| b & 0x7F);
+    let data2 = bytes.get(2).map(|b| b & 0x7F);
+    let legacy = |control_number: u8, value: u8, value2: u8| Event::LegacyMidiCcOut {
+        header,
+        control_number,
+        channel: channel as i8,
+        value: value as i8,
+        value2: value2 as i8,
+    };
+    match status & 0xF0 {
+        NOTE_ON if data2? > 0 => Some(Event::NoteOn {
+            header,
+            channel,
+            pitch: data1? as i16,
+            tuning: 0.0,
+            velocity: velocity_from_midi(data2?),
+            length: 0,
+            note_id: -1,
+        }),
+        NOTE_ON | NOTE_OFF => Some(Event::NoteOff {
+            header,
+            channel,
+            pitch: data1? as i16,
+            velocity: velocity_from_midi(data2?),
+            note_id: -1,
+            tuning: 0.0,
+        }),
+        POLY_PRESSURE => Some(Event::PolyPressure {
+            header,
+            channel,
+            pitch: data1? as i16,
+            pressure: velocity_from_midi(data2?),
+            note_id: -1,
+        }),
+        CONTROL_CHANGE => Some(legacy(data1?, data2?, 0)),
+        PROGRAM_CHANGE => Some(legacy(CTRL_PROGRAM_CHANGE, data1?, 0)),
+        CHANNEL_PRESSURE => Some(legacy(CTRL_AFTER_TOUCH, data1?, 0)),
+        PITCH_BEND => Some(legacy(CTRL_PITCH_BEND, data1?, data2?)),
+        _ => None,
+    }
+}
+
+fn velocity_to_midi(velocity: f32) -> u8 {
+    (velocity.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+fn velocity_from_midi(velocity: u8) -> f32 {
+    velocity as f32 / 127.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{event_to_midi, midi_to_event, Event, CTRL_PITCH_BEND};
+
+    #[test]
+    fn test_note_round_trip() {
+        let bytes = [0x93, 60, 100];
+        let event = midi_to_event(0, 12, &bytes).unwrap();
+        match event {
+            Event::NoteOn { channel, pitch, .. } => assert_eq!((channel, pitch), (3, 60)),
+            _ => panic!("expected a note on"),
+        }
+        let message = event_to_midi(&event).unwrap();
+        assert_eq!(message.as_bytes(), &bytes[..]);
+        assert_eq!(message.get_sample_offset(), 12);
+
+        let note_off = midi_to_event(0, 0, &[0x93, 60, 0]).unwrap();
+        assert_eq!(event_to_midi(&note_off).unwrap().as_bytes(), &[0x83, 60, 0]);
+    }
+
+    #[test]
+    fn test_pitch_bend_and_sysex() {
+        let bytes = [0xE1, 0x12, 0x34];
+        let event = midi_to_event(0, 0, &bytes).unwrap();
+        match event {
+            Event::LegacyMidiCcOut { control_number, .. } => {
+                assert_eq!(control_number, CTRL_PITCH_BEND)
+            }
+            _ => panic!("expected a pitch bend"),
+        }
+        assert_eq!(event_to_midi(&event).unwrap().as_bytes(), &bytes[..]);
+
+        let sysex = [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+        let event = midi_to_event(0, 0, &sysex).unwrap();
+        assert_eq!(event_to_midi(&event).unwrap().as_bytes(), &sysex[..]);
+    }
+}
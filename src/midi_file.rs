@@ -0,0 +1,491 @@
+use std::fs;
+use std::path::Path;
+
+use num_traits::Float;
+
+use crate::ResultErr::{InvalidArgument, ResultFalse};
+use crate::{event_to_midi, midi_to_event, Event, EventList, ProcessData, ResultErr};
+
+const META: u8 = 0xFF;
+const META_END_OF_TRACK: u8 = 0x2F;
+const META_SET_TEMPO: u8 = 0x51;
+const SYSEX: u8 = 0xF0;
+const SYSEX_ESCAPE: u8 = 0xF7;
+
+/// A message of a track at an absolute tick. Meta events are stored as `0xFF`, the meta type and
+/// the data; system exclusive messages start with `0xF0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiFileEvent {
+    pub tick: u64,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MidiTrack {
+    pub events: Vec<MidiFileEvent>,
+}
+
+/// Standard MIDI File with a metrical time division.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiFile {
+    pub format: u16,
+    pub ticks_per_quarter: u16,
+    pub tracks: Vec<MidiTrack>,
+}
+
+impl MidiFile {
+    pub fn new(ticks_per_quarter: u16) -> Self {
+        Self {
+            format: 1,
+            ticks_per_quarter,
+            tracks: vec![],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ResultErr> {
+        match fs::read(path) {
+            Ok(bytes) => Self::read(&bytes),
+            Err(e) => {
+                log::error!("Could not read MIDI file {}: {}", path.display(), e);
+                Err(ResultFalse)
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ResultErr> {
+        match fs::write(path, self.write()) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                log::error!("Could not write MIDI file {}: {}", path.display(), e);
+                Err(ResultFalse)
+            }
+        }
+    }
+
+    /// Files with an SMPTE time division are not supported.
+    pub fn read(bytes: &[u8]) -> Result<Self, ResultErr> {
+        let mut reader = Reader { bytes, pos: 0 };
+        read_file(&mut reader).ok_or(InvalidArgument)
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend_from_slice(&6u32.to_be_bytes());
+        bytes.extend_from_slice(&self.format.to_be_bytes());
+        bytes.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.ticks_per_quarter.to_be_bytes());
+
+        for track in self.tracks.iter() {
+            let mut events: Vec<&MidiFileEvent> = track.events.iter().collect();
+            events.sort_by_key(|event| event.tick);
+
+            let mut data = vec![];
+            let mut tick = 0;
+            for event in events.into_iter().filter(|event| !event.bytes.is_empty()) {
+                write_var_len(&mut data, (event.tick - tick) as u32);
+                tick = event.tick;
+                match event.bytes[0] {
+                    META if event.bytes.len() >= 2 => {
+                        data.extend_from_slice(&event.bytes[..2]);
+                        write_var_len(&mut data, event.bytes.len() as u32 - 2);
+                        data.extend_from_slice(&event.bytes[2..]);
+                    }
+                    SYSEX => {
+                        data.push(SYSEX);
+                        write_var_len(&mut data, event.bytes.len() as u32 - 1);
+                        data.extend_from_slice(&event.bytes[1..]);
+                    }
+                    _ => data.extend_from_slice(&event.bytes),
+                }
+            }
+            data.extend_from_slice(&[0, META, META_END_OF_TRACK, 0]);
+
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&data);
+        }
+        bytes
+    }
+
+    /// Splits the messages of all tracks into blocks of `block_size` samples. `tempo`, in beats
+    /// per minute, is used until the first set tempo meta event. Meta events are left out. Empty
+    /// if `block_size` is 0.
+    pub fn to_blocks(&self, sample_rate: f64, tempo: f64, block_size: usize) -> Vec<MidiBlock> {
+        if block_size == 0 {
+            return vec![];
+        }
+        let mut events: Vec<&MidiFileEvent> = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.events.iter())
+            .collect();
+        events.sort_by_key(|event| event.tick);
+
+        let mut blocks: Vec<MidiBlock> = vec![];
+        let mut seconds_per_tick = 60.0 / (tempo * self.ticks_per_quarter as f64);
+        let mut seconds = 0.0;
+        let mut tick = 0;
+        for event in events {
+            seconds += (event.tick - tick) as f64 * seconds_per_tick;
+            tick = event.tick;
+            match event.bytes.as_slice() {
+                [META, META_SET_TEMPO, a, b, c] => {
+                    let micros_per_quarter = u32::from_be_bytes([0, *a, *b, *c]);
+                    seconds_per_tick =
+                        micros_per_quarter as f64 / 1_000_000.0 / self.ticks_per_quarter as f64;
+                }
+                [META, ..] | [] => (),
+                bytes => {
+                    let sample = (seconds * sample_rate).round() as usize;
+                    let index = sample / block_size;
+                    if blocks.len() <= index {
+                        blocks.resize_with(index + 1, MidiBlock::default);
+                    }
+                    blocks[index]
+                        .messages
+                        .push(((sample % block_size) as i32, bytes.to_vec()));
+                }
+            }
+        }
+        blocks
+    }
+}
+
+/// MIDI messages of one block, at their sample offset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MidiBlock {
+    messages: Vec<(i32, Vec<u8>)>,
+}
+
+impl MidiBlock {
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// The messages as events of the input bus, skipping the ones without an event equivalent.
+    pub fn events(&self, bus_index: i32) -> impl Iterator<Item = Event<'_>> {
+        self.messages
+            .iter()
+            .filter_map(move |(offset, bytes)| midi_to_event(bus_index, *offset, bytes))
+    }
+}
+
+/// Records events into a single track file at a fixed tempo.
+pub struct MidiRecorder {
+    sample_rate: f64,
+    tempo: f64,
+    ticks_per_quarter: u16,
+    position: u64,
+    track: MidiTrack,
+}
+
+impl MidiRecorder {
+    pub fn new(sample_rate: f64, tempo: f64, ticks_per_quarter: u16) -> Self {
+        Self {
+            sample_rate,
+            tempo,
+            ticks_per_quarter,
+            position: 0,
+            track: MidiTrack::default(),
+        }
+    }
+
+    /// Records an event of the current block. Events without a MIDI equivalent are ignored.
+    pub fn record_event(&mut self, event: &Event) {
+        if let Some(message) = event_to_midi(event) {
+            let sample = self.position as f64 + message.get_sample_offset().max(0) as f64;
+            let ticks_per_sample =
+                self.tempo / 60.0 * self.ticks_per_quarter as f64 / self.sample_rate;
+            self.track.events.push(MidiFileEvent {
+                tick: (sample * ticks_per_sample).round() as u64,
+                bytes: message.as_bytes().to_vec(),
+            });
+        }
+    }
+
+    pub fn record_events(&mut self, events: &EventList) {
+        for event in events {
+            self.record_event(&event);
+        }
+    }
+
+    /// Moves to the next block.
+    pub fn advance(&mut self, num_samples: usize) {
+        self.position += num_samples as u64;
+    }
+
+    /// Records the output events of a processed block and moves to the next one.
+    pub fn record_block<T: Float>(&mut self, data: &mut ProcessData<T>) {
+        if let Some(events) = data.get_output_events_mut() {
+            self.record_events(events);
+        }
+        self.advance(data.num_samples());
+    }
+
+    pub fn finish(self) -> MidiFile {
+        let micros_per_quarter = (60_000_000.0 / self.tempo).round() as u32;
+        let mut tempo = vec![META, META_SET_TEMPO];
+        tempo.extend_from_slice(&micros_per_quarter.to_be_bytes()[1..]);
+        let mut track = self.track;
+        track.events.insert(
+            0,
+            MidiFileEvent {
+                tick: 0,
+                bytes: tempo,
+            },
+        );
+        MidiFile {
+            format: 0,
+            ticks_per_quarter: self.ticks_per_quarter,
+            tracks: vec![track],
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn peek_u8(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.take(4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn var_len(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+fn read_file(reader: &mut Reader) -> Option<MidiFile> {
+    if reader.take(4)? != b"MThd" {
+        return None;
+    }
+    let header = reader.u32()? as usize;
+    let format = reader.u16()?;
+    let track_count = reader.u16()?;
+    let division = reader.u16()?;
+    reader.take(header.checked_sub(6)?)?;
+    if division & 0x8000 != 0 {
+        log::error!("MIDI files with an SMPTE time division are not supported");
+        return None;
+    }
+
+    let mut tracks = vec![];
+    while tracks.len() < track_count as usize && !reader.is_empty() {
+        let id = reader.take(4)?;
+        let len = reader.u32()? as usize;
+        let chunk = reader.take(len)?;
+        if id == b"MTrk" {
+            tracks.push(read_track(&mut Reader {
+                bytes: chunk,
+                pos: 0,
+            })?);
+        }
+    }
+    Some(MidiFile {
+        format,
+        ticks_per_quarter: division,
+        tracks,
+    })
+}
+
+fn read_track(reader: &mut Reader) -> Option<MidiTrack> {
+    let mut track = MidiTrack::default();
+    let mut tick = 0u64;
+    let mut running_status = None;
+    while !reader.is_empty() {
+        tick += reader.var_len()? as u64;
+        let bytes = match reader.peek_u8()? {
+            META => {
+                reader.u8()?;
+                let type_ = reader.u8()?;
+                let len = reader.var_len()? as usize;
+                let data = reader.take(len)?;
+                if type_ == META_END_OF_TRACK {
+                    break;
+                }
+                running_status = None;
+                let mut bytes = vec![META, type_];
+                bytes.extend_from_slice(data);
+                bytes
+            }
+            SYSEX => {
+                reader.u8()?;
+                running_status = None;
+                let len = reader.var_len()? as usize;
+                let mut bytes = vec![SYSEX];
+                bytes.extend_from_slice(reader.take(len)?);
+                bytes
+            }
+            SYSEX_ESCAPE => {
+                reader.u8()?;
+                running_status = None;
+                let len = reader.var_len()? as usize;
+                reader.take(len)?.to_vec()
+            }
+            byte => {
+                let status = if byte & 0x80 != 0 {
+                    reader.u8()?
+                } else {
+                    running_status?
+                };
+                running_status = Some(status);
+                let len = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                let mut bytes = vec![status];
+                bytes.extend_from_slice(reader.take(len)?);
+                bytes
+            }
+        };
+        track.events.push(MidiFileEvent { tick, bytes });
+    }
+    Some(track)
+}
+
+fn write_var_len(bytes: &mut Vec<u8>, value: u32) {
+    let mut buffer = [0u8; 5];
+    let mut len = 0;
+    let mut value = value;
+    loop {
+        buffer[len] = (value & 0x7F) as u8;
+        len += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for i in (0..len).rev() {
+        bytes.push(if i > 0 { buffer[i] | 0x80 } else { buffer[i] });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Event, MidiFile, MidiFileEvent, MidiRecorder, MidiTrack, ProcessDataBuilder};
+
+    fn note(tick: u64, status: u8, pitch: u8) -> MidiFileEvent {
+        MidiFileEvent {
+            tick,
+            bytes: vec![status, pitch, 100],
+        }
+    }
+
+    #[test]
+    fn test_write_read() {
+        let mut file = MidiFile::new(480);
+        file.tracks.push(MidiTrack {
+            events: vec![
+                MidiFileEvent {
+                    tick: 0,
+                    bytes: vec![0xFF, 0x51, 0x07, 0xA1, 0x20],
+                },
+                note(0, 0x90, 60),
+                note(200, 0x80, 60),
+                MidiFileEvent {
+                    tick: 100_000,
+                    bytes: vec![0xF0, 0x7E, 0x7F, 0xF7],
+                },
+            ],
+        });
+        assert_eq!(MidiFile::read(&file.write()).ok(), Some(file));
+    }
+
+    #[test]
+    fn test_to_blocks() {
+        let mut file = MidiFile::new(480);
+        file.tracks.push(MidiTrack {
+            events: vec![note(0, 0x90, 60), note(480, 0x80, 60)],
+        });
+        // A quarter note at 120 bpm lasts 24000 samples at 48 kHz.
+        let blocks = file.to_blocks(48000.0, 120.0, 512);
+        assert_eq!(blocks.len(), 24000 / 512 + 1);
+        assert_eq!(blocks[0].len(), 1);
+        let event = blocks[24000 / 512].events(0).next();
+        match event {
+            Some(Event::NoteOff { header, pitch, .. }) => {
+                assert_eq!(header.sample_offset, 24000 % 512);
+                assert_eq!(pitch, 60);
+            }
+            _ => panic!("expected a note off"),
+        }
+    }
+
+    #[test]
+    fn test_running_status_cleared_by_meta() {
+        #[rustfmt::skip]
+        let bytes = [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0x01, 0xE0,
+            b'M', b'T', b'r', b'k', 0, 0, 0, 11,
+            0, 0x90, 60, 100,
+            0, 0xFF, 0x01, 0,
+            0, 60, 0,
+        ];
+        assert!(MidiFile::read(&bytes).is_err());
+        assert!(MidiFile::new(480).to_blocks(48000.0, 120.0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_blocks_through_process_data() {
+        let mut file = MidiFile::new(480);
+        file.tracks.push(MidiTrack {
+            events: vec![note(0, 0x90, 60), note(480, 0x80, 60)],
+        });
+        let mut builder = ProcessDataBuilder::<f32>::new(512).output_bus(2);
+        let mut recorder = MidiRecorder::new(48000.0, 120.0, 480);
+        for block in file.to_blocks(48000.0, 120.0, 512) {
+            assert!(builder.add_midi_block(&block, 0).is_ok());
+            let mut data = builder.build();
+            let in_events = data.get_input_events().unwrap();
+            assert_eq!(in_events.get_event_count() as usize, block.len());
+            // Echo the input events to the output, as a MIDI effect would.
+            let out_events = data.get_output_events_mut().unwrap();
+            for event in block.events(0) {
+                assert!(out_events.add_copied_event(&event).is_ok());
+            }
+            recorder.record_block(&mut data);
+        }
+        assert_eq!(
+            recorder.finish().tracks[0].events[1..],
+            file.tracks[0].events[..]
+        );
+    }
+}
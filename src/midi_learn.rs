@@ -0,0 +1,52 @@
+use vst3_sys::vst::IMidiLearn;
+
+use crate::RestartFlag::MidiCcAssignmentChanged;
+use crate::ResultErr::{NotImplemented, ResultFalse};
+use crate::ResultOk::ResOk;
+use crate::{MidiMapping, ResultErr, ResultOk, VST3EditController};
+
+pub trait MidiLearn: MidiMapping {
+    /// Called by the host for controllers played live. By default binds the controller to the
+    /// parameter armed in the mapping table.
+    fn on_live_midi_controller_input(
+        &mut self,
+        bus_index: i32,
+        channel: i16,
+        midi_controller_number: i16,
+    ) -> Result<ResultOk, ResultErr> {
+        let table = self.get_midi_mapping_table_mut().ok_or(NotImplemented)?;
+        match table.learn(bus_index, channel, midi_controller_number) {
+            Some(_) => Ok(ResOk),
+            None => Err(ResultFalse),
+        }
+    }
+}
+
+impl IMidiLearn for VST3EditController {
+    unsafe fn on_live_midi_controller_input(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        midi_cc: i16,
+    ) -> i32 {
+        let mut plugin_base = self.get_plugin_base().lock().unwrap();
+        let (result, handler) = match plugin_base.as_midi_learn() {
+            Some(midi_learn) => {
+                let result = midi_learn.on_live_midi_controller_input(bus_index, channel, midi_cc);
+                (result, midi_learn.get_component_handler().cloned())
+            }
+            None => return NotImplemented.into(),
+        };
+        // The host queries the assignments again while handling the restart.
+        drop(plugin_base);
+        if result.is_ok() {
+            if let Some(handler) = handler {
+                let _ = handler.restart_component(MidiCcAssignmentChanged as i32);
+            }
+        }
+        match result {
+            Ok(r) => r.into(),
+            Err(r) => r.into(),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use crate::ParameterFlag::IsHidden;
+use crate::{
+    BaseParameter, ParamId, ParameterContainer, ParameterInfoBuilder, PluginBase, Stream,
+    CTRL_PITCH_BEND,
+};
+
+/// Maps MIDI controllers to parameters for `MidiMapping`. Besides the CC numbers, the pseudo
+/// controllers like `CTRL_PITCH_BEND` and `CTRL_AFTER_TOUCH` can be assigned. Assignments are
+/// kept sorted, so the written state only depends on them.
+#[derive(Default)]
+pub struct MidiMappingTable {
+    assignments: BTreeMap<(i32, i16, i16), ParamId>,
+    armed: Option<ParamId>,
+}
+
+impl MidiMappingTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn assign(
+        &mut self,
+        bus_index: i32,
+        channel: i16,
+        midi_controller_number: i16,
+        id: ParamId,
+    ) {
+        self.assignments
+            .insert((bus_index, channel, midi_controller_number), id);
+    }
+
+    /// Assigns the controller on all 16 channels of the bus.
+    pub fn assign_all_channels(
+        &mut self,
+        bus_index: i32,
+        midi_controller_number: i16,
+        id: ParamId,
+    ) {
+        for channel in 0..16 {
+            self.assign(bus_index, channel, midi_controller_number, id);
+        }
+    }
+
+    pub fn unassign(
+        &mut self,
+        bus_index: i32,
+        channel: i16,
+        midi_controller_number: i16,
+    ) -> Option<ParamId> {
+        self.assignments
+            .remove(&(bus_index, channel, midi_controller_number))
+    }
+
+    /// Removes every assignment to the parameter.
+    pub fn unassign_parameter(&mut self, id: ParamId) {
+        self.assignments.retain(|_, assigned| *assigned != id);
+    }
+
+    pub fn get_assignment(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        midi_controller_number: i16,
+    ) -> Option<ParamId> {
+        self.assignments
+            .get(&(bus_index, channel, midi_controller_number))
+            .copied()
+    }
+
+    pub fn get_assignment_count(&self) -> usize {
+        self.assignments.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.assignments.clear();
+    }
+
+    /// Makes the next live controller input reported through `MidiLearn` bind to the parameter.
+    pub fn arm(&mut self, id: ParamId) {
+        self.armed = Some(id);
+    }
+
+    pub fn disarm(&mut self) {
+        self.armed = None;
+    }
+
+    pub fn get_armed(&self) -> Option<ParamId> {
+        self.armed
+    }
+
+    /// Binds the controller to the armed parameter, replacing its previous assignments.
+    pub(crate) fn learn(
+        &mut self,
+        bus_index: i32,
+        channel: i16,
+        midi_controller_number: i16,
+    ) -> Option<ParamId> {
+        let id = self.armed.take()?;
+        self.unassign_parameter(id);
+        self.assign(bus_index, channel, midi_controller_number, id);
+        Some(id)
+    }
+
+    /// Adds a hidden parameter that only receives the controller, for controllers that are not
+    /// meant to be automated like pitch bend, and assigns it.
+    pub fn add_proxy_parameter(
+        &mut self,
+        parameters: &mut ParameterContainer,
+        title: &str,
+        id: ParamId,
+        bus_index: i32,
+        channel: i16,
+        midi_controller_number: i16,
+    ) {
+        let default_value = if midi_controller_number == CTRL_PITCH_BEND as i16 {
+            0.5
+        } else {
+            0.0
+        };
+        let info = ParameterInfoBuilder::new(title, id)
+            .default_normalized_value(default_value)
+            .flags(IsHidden as i32)
+            .build();
+        parameters.add_parameter(BaseParameter::new(info));
+        self.assign(bus_index, channel, midi_controller_number, id);
+    }
+
+    pub fn write(&self, state: &Stream) -> bool {
+        if !state.write::<u32>(self.assignments.len() as u32) {
+            return false;
+        }
+        for ((bus_index, channel, midi_controller_number), id) in self.assignments.iter() {
+            if !state.write::<i32>(*bus_index)
+                || !state.write::<i16>(*channel)
+                || !state.write::<i16>(*midi_controller_number)
+                || !state.write::<u32>((*id).into())
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Replaces the assignments with the ones written by `write`. The table is left untouched if
+    /// the stream holds no table, as in states saved before it existed.
+    pub fn read(&mut self, state: &Stream) -> bool {
+        let count = match state.read::<u32>() {
+            Some(count) => count,
+            None => return false,
+        };
+        let mut assignments = BTreeMap::new();
+        for _ in 0..count {
+            let key = match (
+                state.read::<i32>(),
+                state.read::<i16>(),
+                state.read::<i16>(),
+            ) {
+                (Some(bus_index), Some(channel), Some(number)) => (bus_index, channel, number),
+                _ => return false,
+            };
+            match state.read::<u32>() {
+                Some(id) => assignments.insert(key, ParamId(id)),
+                None => return false,
+            };
+        }
+        self.assignments = assignments;
+        true
+    }
+}
+
+/// Appends the mapping table, if any, to the controller state.
+pub(crate) fn write_midi_mapping(plugin_base: &mut dyn PluginBase, state: &Stream) -> bool {
+    match plugin_base
+        .as_midi_mapping()
+        .and_then(|m| m.get_midi_mapping_table())
+    {
+        Some(table) => table.write(state),
+        None => true,
+    }
+}
+
+/// Returns `true` if a table was restored.
+pub(crate) fn read_midi_mapping(plugin_base: &mut dyn PluginBase, state: &Stream) -> bool {
+    match plugin_base
+        .as_midi_mapping()
+        .and_then(|m| m.get_midi_mapping_table_mut())
+    {
+        Some(table) => table.read(state),
+        None => false,
+    }
+}
@@ -0,0 +1,281 @@
+use std::convert::TryFrom;
+
+use crate::ParameterFlag::IsHidden;
+use crate::ResultErr::{InvalidArgument, ResultFalse};
+use crate::{
+    BaseParameter, Event, NoteExpressionKind, ParamId, ParameterContainer, ParameterInfoBuilder,
+    ResultErr, CTRL_AFTER_TOUCH, CTRL_PITCH_BEND,
+};
+
+const TIMBRE_CONTROLLER: i16 = 74;
+const CHANNEL_COUNT: usize = 16;
+const DIMENSION_COUNT: usize = 3;
+/// Held notes tracked at once, allocated up front.
+const MAX_NOTES: usize = 128;
+/// Range of the tuning note expression in semitones.
+const TUNING_RANGE: f64 = 240.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MpeDimension {
+    /// Offset in semitones.
+    Pitch,
+    /// Between 0 and 1.
+    Pressure,
+    /// Between 0 and 1.
+    Timbre,
+}
+
+impl MpeDimension {
+    fn index(self) -> usize {
+        match self {
+            MpeDimension::Pitch => 0,
+            MpeDimension::Pressure => 1,
+            MpeDimension::Timbre => 2,
+        }
+    }
+}
+
+/// Per-note modulation. `note_id` is -1 when the host did not give an ID to the note.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoteModulation {
+    pub note_id: i32,
+    pub channel: i16,
+    pub pitch: i16,
+    pub dimension: MpeDimension,
+    pub value: f64,
+}
+
+#[derive(Copy, Clone)]
+struct ActiveNote {
+    note_id: i32,
+    channel: i16,
+    pitch: i16,
+}
+
+/// Turns per-channel MPE controllers, received as `MidiMapping` pseudo-parameters, and note
+/// expression events into per-note modulations.
+///
+/// The controller assigns the pseudo-parameters with `get_midi_controller_assignment` and adds
+/// them with `add_parameters`; the processor feeds its events and parameter changes to
+/// `handle_event` and `handle_param_change`.
+pub struct MpeInput {
+    first_param_id: ParamId,
+    pitch_bend_range: f64,
+    notes: Vec<ActiveNote>,
+    channel_values: [[f64; DIMENSION_COUNT]; CHANNEL_COUNT],
+}
+
+impl MpeInput {
+    /// Uses the 48 parameter IDs starting at `first_param_id`, which must all fit in a `u32`.
+    pub fn new(first_param_id: ParamId) -> Result<Self, ResultErr> {
+        let param_count = (CHANNEL_COUNT * DIMENSION_COUNT) as u32;
+        if first_param_id.0.checked_add(param_count - 1).is_none() {
+            return Err(InvalidArgument);
+        }
+        Ok(Self {
+            first_param_id,
+            pitch_bend_range: 48.0,
+            notes: Vec::with_capacity(MAX_NOTES),
+            channel_values: [[0.0; DIMENSION_COUNT]; CHANNEL_COUNT],
+        })
+    }
+
+    /// Range of the pitch bend of the member channels in semitones.
+    pub fn set_pitch_bend_range(&mut self, semitones: f64) {
+        self.pitch_bend_range = semitones;
+    }
+
+    pub fn get_parameter_id(&self, channel: i16, dimension: MpeDimension) -> Option<ParamId> {
+        if channel < 0 || channel as usize >= CHANNEL_COUNT {
+            return None;
+        }
+        let offset = channel as u32 * DIMENSION_COUNT as u32 + dimension.index() as u32;
+        self.first_param_id.0.checked_add(offset).map(ParamId)
+    }
+
+    pub fn get_midi_controller_assignment(
+        &self,
+        channel: i16,
+        midi_controller_number: i16,
+    ) -> Result<ParamId, ResultErr> {
+        let dimension = match midi_controller_number {
+            n if n == CTRL_PITCH_BEND as i16 => MpeDimension::Pitch,
+            n if n == CTRL_AFTER_TOUCH as i16 => MpeDimension::Pressure,
+            TIMBRE_CONTROLLER => MpeDimension::Timbre,
+            _ => return Err(ResultFalse),
+        };
+        self.get_parameter_id(channel, dimension).ok_or(ResultFalse)
+    }
+
+    pub fn add_parameters(&self, parameters: &mut ParameterContainer) {
+        let dimensions = [
+            ("Pitch Bend", MpeDimension::Pitch, 0.5),
+            ("Pressure", MpeDimension::Pressure, 0.0),
+            ("Timbre", MpeDimension::Timbre, 0.0),
+        ];
+        for channel in 0..CHANNEL_COUNT as i16 {
+            for (title, dimension, default_value) in dimensions.iter() {
+                let id = match self.get_parameter_id(channel, *dimension) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                let info =
+                    ParameterInfoBuilder::new(&format!("MPE {} Ch. {}", title, channel + 1), id)
+                        .default_normalized_value(*default_value)
+                        .flags(IsHidden as i32)
+                        .build();
+                parameters.add_parameter(BaseParameter::new(info));
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.notes.clear();
+        self.channel_values = [[0.0; DIMENSION_COUNT]; CHANNEL_COUNT];
+    }
+
+    /// Returns `false` if `id` is not one of the MPE pseudo-parameters.
+    pub fn handle_param_change(
+        &mut self,
+        id: ParamId,
+        value: f64,
+        mut modulation: impl FnMut(NoteModulation),
+    ) -> bool {
+        if id.0 < self.first_param_id.0 {
+            return false;
+        }
+        let offset = (id.0 - self.first_param_id.0) as usize;
+        if offset >= CHANNEL_COUNT * DIMENSION_COUNT {
+            return false;
+        }
+        let channel = offset / DIMENSION_COUNT;
+        let dimension = match offset % DIMENSION_COUNT {
+            0 => MpeDimension::Pitch,
+            1 => MpeDimension::Pressure,
+            _ => MpeDimension::Timbre,
+        };
+        let value = match dimension {
+            MpeDimension::Pitch => (value * 2.0 - 1.0) * self.pitch_bend_range,
+            _ => value,
+        };
+        self.channel_values[channel][dimension.index()] = value;
+        for note in self.notes.iter().filter(|n| n.channel as usize == channel) {
+            modulation(NoteModulation {
+                note_id: note.note_id,
+                channel: note.channel,
+                pitch: note.pitch,
+                dimension,
+                value,
+            });
+        }
+        true
+    }
+
+    pub fn handle_event(&mut self, event: &Event, mut modulation: impl FnMut(NoteModulation)) {
+        match *event {
+            Event::NoteOn {
+                channel,
+                pitch,
+                note_id,
+                ..
+            } => {
+                let note = ActiveNote {
+                    note_id,
+                    channel,
+                    pitch,
+                };
+                // The oldest note stops being modulated rather than growing the list on the
+                // audio thread.
+                if self.notes.len() >= MAX_NOTES {
+                    self.notes.remove(0);
+                }
+                self.notes.push(note);
+                // The member channel controllers are usually sent before the note on.
+                if let Some(values) = self.channel_values.get(channel as usize) {
+                    for dimension in [
+                        MpeDimension::Pitch,
+                        MpeDimension::Pressure,
+                        MpeDimension::Timbre,
+                    ]
+                    .iter()
+                    {
+                        modulation(NoteModulation {
+                            note_id,
+                            channel,
+                            pitch,
+                            dimension: *dimension,
+                            value: values[dimension.index()],
+                        });
+                    }
+                }
+            }
+            Event::NoteOff {
+                channel,
+                pitch,
+                note_id,
+                ..
+            } => {
+                self.notes.retain(|note| {
+                    if note_id != -1 {
+                        note.note_id != note_id
+                    } else {
+                        note.channel != channel || note.pitch != pitch
+                    }
+                });
+            }
+            Event::PolyPressure {
+                channel,
+                pitch,
+                pressure,
+                note_id,
+                ..
+            } => modulation(NoteModulation {
+                note_id,
+                channel,
+                pitch,
+                dimension: MpeDimension::Pressure,
+                value: pressure as f64,
+            }),
+            Event::NoteExpressionValue {
+                type_id,
+                note_id,
+                value,
+                ..
+            } => {
+                let (dimension, value) = match NoteExpressionKind::try_from(type_id) {
+                    Ok(NoteExpressionKind::Tuning) => {
+                        (MpeDimension::Pitch, (value - 0.5) * TUNING_RANGE)
+                    }
+                    Ok(NoteExpressionKind::Brightness) => (MpeDimension::Timbre, value),
+                    _ => return,
+                };
+                if let Some(note) = self.notes.iter().find(|note| note.note_id == note_id) {
+                    modulation(NoteModulation {
+                        note_id,
+                        channel: note.channel,
+                        pitch: note.pitch,
+                        dimension,
+                        value,
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MpeDimension, MpeInput, ParamId};
+
+    #[test]
+    fn test_parameter_id_range() {
+        let mpe = MpeInput::new(ParamId(u32::MAX - 47)).unwrap();
+        assert_eq!(
+            mpe.get_parameter_id(15, MpeDimension::Timbre),
+            Some(ParamId(u32::MAX))
+        );
+        assert_eq!(mpe.get_parameter_id(16, MpeDimension::Pitch), None);
+        assert!(MpeInput::new(ParamId(u32::MAX - 46)).is_err());
+    }
+}
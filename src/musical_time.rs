@@ -0,0 +1,308 @@
+use crate::ProcessContext;
+
+/// Used when the host does not provide a tempo.
+const DEFAULT_TEMPO: f64 = 120.0;
+/// Tolerance in quarter notes when comparing musical positions.
+const EPSILON: f64 = 1e-9;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoteModifier {
+    Straight,
+    Dotted,
+    Triplet,
+}
+
+/// A note length like 1/4, 1/8 dotted or 1/16 triplet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoteDivision {
+    pub denominator: u32,
+    pub modifier: NoteModifier,
+}
+
+impl NoteDivision {
+    pub fn new(denominator: u32) -> Self {
+        Self {
+            denominator,
+            modifier: NoteModifier::Straight,
+        }
+    }
+
+    pub fn dotted(denominator: u32) -> Self {
+        Self {
+            denominator,
+            modifier: NoteModifier::Dotted,
+        }
+    }
+
+    pub fn triplet(denominator: u32) -> Self {
+        Self {
+            denominator,
+            modifier: NoteModifier::Triplet,
+        }
+    }
+
+    /// 0 for a denominator of 0.
+    pub fn to_quarter_notes(&self) -> f64 {
+        if self.denominator == 0 {
+            return 0.0;
+        }
+        let straight = 4.0 / self.denominator as f64;
+        match self.modifier {
+            NoteModifier::Straight => straight,
+            NoteModifier::Dotted => straight * 1.5,
+            NoteModifier::Triplet => straight * 2.0 / 3.0,
+        }
+    }
+
+    /// 0 if `tempo` is not positive.
+    pub fn to_seconds(&self, tempo: f64) -> f64 {
+        if tempo <= 0.0 {
+            return 0.0;
+        }
+        self.to_quarter_notes() * 60.0 / tempo
+    }
+
+    pub fn to_samples(&self, tempo: f64, sample_rate: f64) -> f64 {
+        self.to_seconds(tempo) * sample_rate
+    }
+
+    /// Frequency of an LFO with one cycle per note, 0 if the note has no length.
+    pub fn to_hertz(&self, tempo: f64) -> f64 {
+        let seconds = self.to_seconds(tempo);
+        if seconds > 0.0 {
+            1.0 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl ProcessContext {
+    /// `None` if the host did not provide a positive tempo.
+    pub fn samples_per_quarter(&self) -> Option<f64> {
+        let tempo = self.tempo.filter(|tempo| *tempo > 0.0)?;
+        Some(60.0 / tempo * self.sample_rate)
+    }
+
+    /// Sample offset of the first beat starting in a block of `num_samples`, if any.
+    pub fn next_beat_offset(&self, num_samples: usize) -> Option<usize> {
+        let position = self.project_time_music?;
+        let denominator = self.time_signature?.denominator;
+        if denominator <= 0 {
+            return None;
+        }
+        let beat_length = 4.0 / denominator as f64;
+        let next_beat = next_multiple(position, 0.0, beat_length);
+        self.offset_of(next_beat, num_samples)
+    }
+
+    /// Sample offset of the first bar starting in a block of `num_samples`, if any.
+    pub fn next_bar_offset(&self, num_samples: usize) -> Option<usize> {
+        let position = self.project_time_music?;
+        let time_signature = self.time_signature?;
+        if time_signature.numerator <= 0 || time_signature.denominator <= 0 {
+            return None;
+        }
+        let bar_length = time_signature.numerator as f64 * 4.0 / time_signature.denominator as f64;
+        let bar_start = self.bar_position_music.unwrap_or(0.0);
+        let next_bar = next_multiple(position, bar_start, bar_length);
+        self.offset_of(next_bar, num_samples)
+    }
+
+    fn offset_of(&self, quarter_notes: f64, num_samples: usize) -> Option<usize> {
+        let offset = (quarter_notes - self.project_time_music?) * self.samples_per_quarter()?;
+        let offset = offset.round().max(0.0) as usize;
+        if offset < num_samples {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+}
+
+/// Smallest `origin + k * length` not before `position`.
+fn next_multiple(position: f64, origin: f64, length: f64) -> f64 {
+    if length <= 0.0 {
+        return position;
+    }
+    let count = ((position - origin) / length - EPSILON).ceil();
+    origin + count * length
+}
+
+/// Follows the musical position across blocks. When the host provides no position it is
+/// extrapolated from the last known tempo, and jumps of the host position, e.g. at the end of a
+/// loop, are reported by `has_jumped`.
+pub struct PpqTracker {
+    sample_rate: f64,
+    tempo: f64,
+    position: f64,
+    expected: Option<f64>,
+    jumped: bool,
+}
+
+impl PpqTracker {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            tempo: DEFAULT_TEMPO,
+            position: 0.0,
+            expected: None,
+            jumped: false,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        if sample_rate > 0.0 {
+            self.sample_rate = sample_rate;
+        }
+    }
+
+    /// Called at the start of every block. The position only advances while the transport is
+    /// playing or when there is no context at all. A tempo or sample rate that is not positive
+    /// is ignored and the last valid one kept.
+    pub fn update(&mut self, context: Option<&ProcessContext>, num_samples: usize) {
+        let mut advance = true;
+        if let Some(context) = context {
+            if context.sample_rate > 0.0 {
+                self.sample_rate = context.sample_rate;
+            }
+            if let Some(tempo) = context.tempo.filter(|tempo| *tempo > 0.0) {
+                self.tempo = tempo;
+            }
+            advance = context.playing;
+        }
+        let samples_per_quarter = 60.0 / self.tempo * self.sample_rate;
+
+        match context.and_then(|context| context.project_time_music) {
+            Some(position) => {
+                // Hosts round positions, so allow an error of about one sample.
+                let tolerance = 1.5 / samples_per_quarter;
+                self.jumped = match self.expected {
+                    Some(expected) => (position - expected).abs() > tolerance,
+                    None => false,
+                };
+                self.position = position;
+            }
+            None => {
+                self.jumped = false;
+                self.position = self.expected.unwrap_or(self.position);
+            }
+        }
+
+        let length = if advance {
+            num_samples as f64 / samples_per_quarter
+        } else {
+            0.0
+        };
+        self.expected = Some(self.position + length);
+    }
+
+    /// Position at the start of the block in quarter notes.
+    pub fn get_position(&self) -> f64 {
+        self.position
+    }
+
+    pub fn get_position_at(&self, sample_offset: usize) -> f64 {
+        self.position + sample_offset as f64 * self.tempo / 60.0 / self.sample_rate
+    }
+
+    pub fn get_tempo(&self) -> f64 {
+        self.tempo
+    }
+
+    /// `true` if the position of this block does not follow the previous one.
+    pub fn has_jumped(&self) -> bool {
+        self.jumped
+    }
+
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+        self.expected = None;
+        self.jumped = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NoteDivision, PpqTracker, ProcessContext, TimeSignature};
+
+    fn context(position: f64, tempo: f64) -> ProcessContext {
+        ProcessContext {
+            sample_rate: 48000.0,
+            project_time_samples: 0,
+            playing: true,
+            cycle_active: false,
+            recording: false,
+            system_time: None,
+            continuous_time_samples: None,
+            project_time_music: Some(position),
+            bar_position_music: Some(0.0),
+            cycle_music: None,
+            tempo: Some(tempo),
+            time_signature: Some(TimeSignature {
+                numerator: 4,
+                denominator: 4,
+            }),
+            chord: None,
+            smpte: None,
+            samples_to_next_clock: None,
+        }
+    }
+
+    #[test]
+    fn test_note_division() {
+        assert_eq!(NoteDivision::new(4).to_samples(120.0, 48000.0), 24000.0);
+        assert_eq!(NoteDivision::dotted(8).to_quarter_notes(), 0.75);
+        assert!((NoteDivision::triplet(4).to_samples(120.0, 48000.0) - 16000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_next_beat_and_bar() {
+        let context = context(3.5, 120.0);
+        assert_eq!(context.next_beat_offset(16384), Some(12000));
+        assert_eq!(context.next_bar_offset(16384), Some(12000));
+        assert_eq!(context.next_beat_offset(512), None);
+    }
+
+    #[test]
+    fn test_invalid_tempo_and_time_signature() {
+        assert_eq!(NoteDivision::new(0).to_hertz(120.0), 0.0);
+        assert_eq!(NoteDivision::new(4).to_hertz(0.0), 0.0);
+        let mut context = context(3.5, 0.0);
+        assert_eq!(context.samples_per_quarter(), None);
+        context.tempo = Some(120.0);
+        context.time_signature = Some(TimeSignature {
+            numerator: 4,
+            denominator: 0,
+        });
+        assert_eq!(context.next_beat_offset(16384), None);
+        assert_eq!(context.next_bar_offset(16384), None);
+        context.time_signature = Some(TimeSignature {
+            numerator: 0,
+            denominator: 4,
+        });
+        assert_eq!(context.next_beat_offset(16384), Some(12000));
+        assert_eq!(context.next_bar_offset(16384), None);
+    }
+
+    #[test]
+    fn test_ppq_tracker() {
+        let mut tracker = PpqTracker::new(48000.0);
+        // 24000 samples are one quarter note at 120 bpm.
+        tracker.update(Some(&context(1.0, 120.0)), 24000);
+        assert!(!tracker.has_jumped());
+        tracker.update(Some(&context(2.0, 120.0)), 24000);
+        assert!(!tracker.has_jumped());
+        // The position is extrapolated without a context and the tempo of 0 is ignored.
+        tracker.update(None, 12000);
+        assert_eq!(tracker.get_position(), 3.0);
+        tracker.update(Some(&context(3.5, 0.0)), 12000);
+        assert!(!tracker.has_jumped());
+        assert_eq!(tracker.get_tempo(), 120.0);
+        assert_eq!(tracker.get_position_at(12000), 4.0);
+        // Back to the start of a loop.
+        tracker.update(Some(&context(1.0, 120.0)), 24000);
+        assert!(tracker.has_jumped());
+        assert_eq!(tracker.get_position(), 1.0);
+    }
+}
@@ -0,0 +1,395 @@
+use std::convert::TryFrom;
+
+use widestring::U16CStr;
+
+use vst3_sys::vst::INoteExpressionController;
+
+use crate::ResultErr::{InvalidArgument, NotImplemented};
+use crate::ResultOk::ResOk;
+use crate::{wstrcpy, EditController, ParamId, ResultErr, VST3EditController};
+
+pub const NOTE_EXPRESSION_CUSTOM_START: u32 = 100000;
+pub const NOTE_EXPRESSION_CUSTOM_END: u32 = 200000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoteExpressionKind {
+    Volume,
+    Pan,
+    Tuning,
+    Vibrato,
+    Expression,
+    Brightness,
+    Text,
+    Phoneme,
+    /// Plug-in defined type, between `NOTE_EXPRESSION_CUSTOM_START` and
+    /// `NOTE_EXPRESSION_CUSTOM_END`.
+    Custom(u32),
+}
+
+impl TryFrom<u32> for NoteExpressionKind {
+    type Error = ResultErr;
+
+    /// IDs outside of the predefined types and the custom range are rejected.
+    fn try_from(type_id: u32) -> Result<Self, Self::Error> {
+        match type_id {
+            0 => Ok(NoteExpressionKind::Volume),
+            1 => Ok(NoteExpressionKind::Pan),
+            2 => Ok(NoteExpressionKind::Tuning),
+            3 => Ok(NoteExpressionKind::Vibrato),
+            4 => Ok(NoteExpressionKind::Expression),
+            5 => Ok(NoteExpressionKind::Brightness),
+            6 => Ok(NoteExpressionKind::Text),
+            7 => Ok(NoteExpressionKind::Phoneme),
+            NOTE_EXPRESSION_CUSTOM_START..=NOTE_EXPRESSION_CUSTOM_END => {
+                Ok(NoteExpressionKind::Custom(type_id))
+            }
+            _ => Err(InvalidArgument),
+        }
+    }
+}
+
+impl From<NoteExpressionKind> for u32 {
+    fn from(kind: NoteExpressionKind) -> Self {
+        match kind {
+            NoteExpressionKind::Volume => 0,
+            NoteExpressionKind::Pan => 1,
+            NoteExpressionKind::Tuning => 2,
+            NoteExpressionKind::Vibrato => 3,
+            NoteExpressionKind::Expression => 4,
+            NoteExpressionKind::Brightness => 5,
+            NoteExpressionKind::Text => 6,
+            NoteExpressionKind::Phoneme => 7,
+            NoteExpressionKind::Custom(type_id) => type_id,
+        }
+    }
+}
+
+pub enum NoteExpressionFlag {
+    IsBipolar = 1,
+    IsOneShot = 1 << 1,
+    IsAbsolute = 1 << 2,
+    AssociatedParameterIdValid = 1 << 3,
+}
+
+/// Normalized values of a note expression type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoteExpressionValueDescription {
+    pub default_value: f64,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub step_count: i32,
+}
+
+impl Default for NoteExpressionValueDescription {
+    fn default() -> Self {
+        Self {
+            default_value: 0.5,
+            minimum: 0.0,
+            maximum: 1.0,
+            step_count: 0,
+        }
+    }
+}
+
+pub struct NoteExpressionTypeInfo {
+    pub kind: NoteExpressionKind,
+    pub title: String,
+    pub short_title: Option<String>,
+    pub units: Option<String>,
+    pub unit_id: i32,
+    pub value_desc: NoteExpressionValueDescription,
+    pub associated_parameter_id: Option<ParamId>,
+    pub flags: i32,
+}
+
+impl NoteExpressionTypeInfo {
+    pub fn get_info(&self) -> vst3_sys::vst::NoteExpressionTypeInfo {
+        let mut flags = self.flags;
+        if self.associated_parameter_id.is_some() {
+            flags |= NoteExpressionFlag::AssociatedParameterIdValid as i32;
+        }
+        let mut info = vst3_sys::vst::NoteExpressionTypeInfo {
+            type_id: self.kind.into(),
+            title: [0; 128],
+            short_title: [0; 128],
+            units: [0; 128],
+            unit_id: self.unit_id,
+            value_desc: vst3_sys::vst::NoteExpressionValueDescription {
+                default_value: self.value_desc.default_value,
+                minimum: self.value_desc.minimum,
+                maximum: self.value_desc.maximum,
+                step_count: self.value_desc.step_count,
+            },
+            associated_parameter_id: match self.associated_parameter_id {
+                Some(id) => id.into(),
+                None => u32::MAX,
+            },
+            flags,
+        };
+
+        unsafe {
+            wstrcpy(&self.title, info.title.as_mut_ptr());
+            if let Some(short_title) = &self.short_title {
+                wstrcpy(short_title, info.short_title.as_mut_ptr());
+            }
+            if let Some(units) = &self.units {
+                wstrcpy(units, info.units.as_mut_ptr());
+            }
+        }
+
+        info
+    }
+}
+
+pub struct NoteExpressionTypeInfoBuilder {
+    kind: NoteExpressionKind,
+    title: String,
+    short_title: Option<String>,
+    units: Option<String>,
+    unit_id: i32,
+    value_desc: NoteExpressionValueDescription,
+    associated_parameter_id: Option<ParamId>,
+    flags: i32,
+}
+
+impl NoteExpressionTypeInfoBuilder {
+    pub fn new(title: &str, kind: NoteExpressionKind) -> Self {
+        Self {
+            kind,
+            title: title.to_string(),
+            short_title: None,
+            units: None,
+            unit_id: crate::ROOT_UNIT_ID,
+            value_desc: NoteExpressionValueDescription::default(),
+            associated_parameter_id: None,
+            flags: 0,
+        }
+    }
+
+    pub fn short_title(mut self, short_title: &str) -> Self {
+        self.short_title = Some(short_title.to_string());
+        self
+    }
+
+    pub fn units(mut self, units: &str) -> Self {
+        self.units = Some(units.to_string());
+        self
+    }
+
+    pub fn unit_id(mut self, unit_id: i32) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    pub fn value_desc(mut self, value_desc: NoteExpressionValueDescription) -> Self {
+        self.value_desc = value_desc;
+        self
+    }
+
+    pub fn associated_parameter_id(mut self, id: ParamId) -> Self {
+        self.associated_parameter_id = Some(id);
+        self
+    }
+
+    pub fn flags(mut self, flags: i32) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn build(self) -> NoteExpressionTypeInfo {
+        NoteExpressionTypeInfo {
+            kind: self.kind,
+            title: self.title,
+            short_title: self.short_title,
+            units: self.units,
+            unit_id: self.unit_id,
+            value_desc: self.value_desc,
+            associated_parameter_id: self.associated_parameter_id,
+            flags: self.flags,
+        }
+    }
+}
+
+pub trait NoteExpressionController: EditController {
+    /// Note expression types supported on a channel of an event input bus.
+    fn get_note_expression_types(
+        &self,
+        _bus_index: i32,
+        _channel: i16,
+    ) -> &[NoteExpressionTypeInfo] {
+        &[]
+    }
+
+    fn get_note_expression_count(&self, bus_index: i32, channel: i16) -> Result<usize, ResultErr> {
+        Ok(self.get_note_expression_types(bus_index, channel).len())
+    }
+
+    fn get_note_expression_info(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        note_expression_index: usize,
+    ) -> Result<&NoteExpressionTypeInfo, ResultErr> {
+        match self
+            .get_note_expression_types(bus_index, channel)
+            .get(note_expression_index)
+        {
+            Some(info) => Ok(info),
+            None => Err(InvalidArgument),
+        }
+    }
+
+    /// The default prints the normalized value: the units of `NoteExpressionTypeInfo` describe
+    /// the plain value, which only the plug-in knows how to compute.
+    fn get_note_expression_string_by_value(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        kind: NoteExpressionKind,
+        value_normalized: f64,
+    ) -> Result<String, ResultErr> {
+        let types = self.get_note_expression_types(bus_index, channel);
+        match types.iter().find(|info| info.kind == kind) {
+            Some(_) => Ok(format!("{:.4}", value_normalized)),
+            None => Err(InvalidArgument),
+        }
+    }
+
+    fn get_note_expression_value_by_string(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        kind: NoteExpressionKind,
+        string: &str,
+    ) -> Result<f64, ResultErr> {
+        let types = self.get_note_expression_types(bus_index, channel);
+        if !types.iter().any(|info| info.kind == kind) {
+            return Err(InvalidArgument);
+        }
+        let value = string.split_whitespace().next().unwrap_or("");
+        match value.parse::<f64>() {
+            Ok(value) => Ok(value),
+            Err(_) => Err(InvalidArgument),
+        }
+    }
+}
+
+impl INoteExpressionController for VST3EditController {
+    unsafe fn get_note_expression_count(&self, bus_index: i32, channel: i16) -> i32 {
+        if let Some(controller) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_note_expression_controller()
+        {
+            return match controller.get_note_expression_count(bus_index, channel) {
+                Ok(count) => count as i32,
+                Err(_) => 0,
+            };
+        }
+        0
+    }
+
+    unsafe fn get_note_expression_info(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        note_expression_index: i32,
+        info: *mut vst3_sys::vst::NoteExpressionTypeInfo,
+    ) -> i32 {
+        if let Some(controller) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_note_expression_controller()
+        {
+            if note_expression_index < 0 || info.is_null() {
+                return InvalidArgument.into();
+            }
+            return match controller.get_note_expression_info(
+                bus_index,
+                channel,
+                note_expression_index as usize,
+            ) {
+                Ok(type_info) => {
+                    *info = type_info.get_info();
+                    ResOk.into()
+                }
+                Err(r) => r.into(),
+            };
+        }
+        NotImplemented.into()
+    }
+
+    unsafe fn get_note_expression_string_by_value(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        id: u32,
+        value_normalized: f64,
+        string: *mut i16,
+    ) -> i32 {
+        if let Some(controller) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_note_expression_controller()
+        {
+            if string.is_null() {
+                return InvalidArgument.into();
+            }
+            let kind = match NoteExpressionKind::try_from(id) {
+                Ok(kind) => kind,
+                Err(r) => return r.into(),
+            };
+            return match controller.get_note_expression_string_by_value(
+                bus_index,
+                channel,
+                kind,
+                value_normalized,
+            ) {
+                Ok(value_string) => {
+                    wstrcpy(&value_string, string);
+                    ResOk.into()
+                }
+                Err(r) => r.into(),
+            };
+        }
+        NotImplemented.into()
+    }
+
+    unsafe fn get_note_expression_value_by_string(
+        &self,
+        bus_index: i32,
+        channel: i16,
+        id: u32,
+        string: *const i16,
+        value_normalized: *mut f64,
+    ) -> i32 {
+        if let Some(controller) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_note_expression_controller()
+        {
+            if string.is_null() || value_normalized.is_null() {
+                return InvalidArgument.into();
+            }
+            let kind = match NoteExpressionKind::try_from(id) {
+                Ok(kind) => kind,
+                Err(r) => return r.into(),
+            };
+            let string = U16CStr::from_ptr_str(string as *const u16).to_string_lossy();
+            return match controller
+                .get_note_expression_value_by_string(bus_index, channel, kind, &string)
+            {
+                Ok(value) => {
+                    *value_normalized = value;
+                    ResOk.into()
+                }
+                Err(r) => r.into(),
+            };
+        }
+        NotImplemented.into()
+    }
+}
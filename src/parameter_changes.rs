@@ -6,7 +6,7 @@ use vst3_sys::base::kResultOk;
 use vst3_sys::vst::{IParamValueQueue, IParameterChanges};
 
 use crate::ResultOk::ResOk;
-use crate::{ResultErr, ResultOk, Unknown, UID};
+use crate::{ParamId, ResultErr, ResultOk, Unknown, UID};
 
 pub struct ParameterChanges {
     inner: ComPtr<dyn IParameterChanges>,
@@ -40,23 +40,19 @@ impl ParameterChanges {
         ParamValueQueue::from_raw(ptr)
     }
 
-    pub fn add_parameter_data(&self, id: &usize, index: &mut usize) -> Option<ParamValueQueue> {
-        if *id > u32::MAX as usize {
-            log::trace!(
-                "ParameterChanges::add_parameter_data(): value is too big! {}usize > {}u32",
-                *id,
-                u32::MAX
-            );
-            return None;
-        }
-        let mut ptr;
+    pub fn add_parameter_data(&self, id: ParamId) -> Option<(ParamValueQueue, usize)> {
+        let id: u32 = id.into();
+        let mut index = 0i32;
+        let ptr;
         unsafe {
-            ptr = self.inner.add_parameter_data(
-                id as *const usize as *const u32,
-                index as *mut usize as *mut i32,
-            );
+            ptr = self
+                .inner
+                .add_parameter_data(&id as *const u32, &mut index as *mut i32);
+        }
+        match ParamValueQueue::from_raw(ptr) {
+            Some(queue) if index >= 0 => Some((queue, index as usize)),
+            _ => None,
         }
-        ParamValueQueue::from_raw(ptr)
     }
 }
 
@@ -82,8 +78,8 @@ impl ParamValueQueue {
         }
     }
 
-    pub fn get_parameter_id(&self) -> usize {
-        unsafe { self.inner.get_parameter_id() as usize }
+    pub fn get_parameter_id(&self) -> ParamId {
+        unsafe { ParamId(self.inner.get_parameter_id()) }
     }
 
     pub fn get_point_count(&self) -> i32 {
@@ -1,11 +1,27 @@
 use std::collections::HashMap;
 
-use crate::ParameterFlag::CanAutomate;
-use crate::ResultErr::InvalidArgument;
-use crate::{wstrcpy, ResultErr, ROOT_UNIT_ID};
+use crate::ParameterFlag::{CanAutomate, IsProgramChange, IsReadOnly};
+use crate::ResultErr::{InvalidArgument, ResultFalse};
+use crate::ResultOk::ResOk;
+use crate::{wstrcpy, ComponentHandler, EditHistory, ResultErr, ResultOk, Stream, ROOT_UNIT_ID};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ParamId(pub u32);
+
+impl From<u32> for ParamId {
+    fn from(id: u32) -> Self {
+        ParamId(id)
+    }
+}
+
+impl From<ParamId> for u32 {
+    fn from(id: ParamId) -> Self {
+        id.0
+    }
+}
 
 pub struct ParameterInfo {
-    pub id: u32,
+    pub id: ParamId,
     pub title: String,
     pub short_title: Option<String>,
     pub units: Option<String>,
@@ -18,7 +34,7 @@ pub struct ParameterInfo {
 impl ParameterInfo {
     pub fn get_info(&self) -> vst3_sys::vst::ParameterInfo {
         let mut p_info = vst3_sys::vst::ParameterInfo {
-            id: self.id,
+            id: self.id.into(),
             title: [0; 128],
             short_title: [0; 128],
             units: [0; 128],
@@ -48,12 +64,13 @@ pub enum ParameterFlag {
     IsReadOnly = vst3_sys::vst::ParameterFlags::kIsReadOnly as isize,
     IsWrapAround = vst3_sys::vst::ParameterFlags::kIsWrapAround as isize,
     IsList = vst3_sys::vst::ParameterFlags::kIsList as isize,
+    IsHidden = vst3_sys::vst::ParameterFlags::kIsHidden as isize,
     IsProgramChange = vst3_sys::vst::ParameterFlags::kIsProgramChange as isize,
     IsBypass = vst3_sys::vst::ParameterFlags::kIsBypass as isize,
 }
 
 pub struct ParameterInfoBuilder {
-    id: usize,
+    id: ParamId,
     title: String,
     short_title: Option<String>,
     units: Option<String>,
@@ -64,7 +81,7 @@ pub struct ParameterInfoBuilder {
 }
 
 impl ParameterInfoBuilder {
-    pub fn new(title: &str, id: usize) -> Self {
+    pub fn new(title: &str, id: ParamId) -> Self {
         Self {
             id,
             title: title.to_string(),
@@ -109,7 +126,7 @@ impl ParameterInfoBuilder {
 
     pub fn build(self) -> ParameterInfo {
         let mut info = ParameterInfo {
-            id: self.id as u32,
+            id: self.id,
             title: self.title,
             short_title: self.short_title,
             units: self.units,
@@ -126,8 +143,8 @@ impl ParameterInfoBuilder {
 pub trait Parameter {
     fn get_info(&self) -> &ParameterInfo;
     fn get_info_mut(&mut self) -> &mut ParameterInfo;
-    fn set_unit_id(&mut self, id: u32);
-    fn get_unit_id(&self) -> u32;
+    fn set_unit_id(&mut self, id: i32);
+    fn get_unit_id(&self) -> i32;
     fn set_normalized(&mut self, v: f64);
     fn get_normalized(&self) -> f64;
     fn set_precision(&mut self, val: usize);
@@ -186,12 +203,12 @@ impl Parameter for BaseParameter {
         &mut self.info
     }
 
-    fn set_unit_id(&mut self, id: u32) {
-        self.info.id = id;
+    fn set_unit_id(&mut self, id: i32) {
+        self.info.unit_id = id;
     }
 
-    fn get_unit_id(&self) -> u32 {
-        self.info.id
+    fn get_unit_id(&self) -> i32 {
+        self.info.unit_id
     }
 
     fn set_normalized(&mut self, norm_value: f64) {
@@ -220,7 +237,9 @@ impl Parameter for BaseParameter {
 
 pub struct ParameterContainer {
     params: Vec<Box<dyn Parameter>>,
-    id_to_index: HashMap<usize, usize>,
+    id_to_index: HashMap<ParamId, usize>,
+    component_handler: Option<ComponentHandler>,
+    edit_history: Option<EditHistory>,
 }
 
 impl ParameterContainer {
@@ -228,12 +247,159 @@ impl ParameterContainer {
         Self {
             params: vec![],
             id_to_index: HashMap::new(),
+            component_handler: None,
+            edit_history: None,
         }
     }
 
+    pub fn set_component_handler(&mut self, handler: Option<ComponentHandler>) {
+        self.component_handler = handler;
+    }
+
+    pub fn get_component_handler(&self) -> Option<&ComponentHandler> {
+        self.component_handler.as_ref()
+    }
+
+    /// Edits are only recorded when a history is set.
+    pub fn set_edit_history(&mut self, edit_history: Option<EditHistory>) {
+        self.edit_history = edit_history;
+    }
+
+    pub fn get_edit_history(&self) -> Option<&EditHistory> {
+        self.edit_history.as_ref()
+    }
+
+    pub(crate) fn get_edit_history_mut(&mut self) -> Option<&mut EditHistory> {
+        self.edit_history.as_mut()
+    }
+
+    /// Starts an edit of the parameter `id` and sends `begin_edit` to the host. Edits until the
+    /// matching `end_edit` form a single undo step.
+    pub fn begin_edit(&mut self, id: ParamId) -> Result<ResultOk, ResultErr> {
+        if self.get_parameter(id).is_none() {
+            log::trace!(
+                "ParameterContainer::begin_edit(): unknown parameter id {:?}",
+                id
+            );
+            return Err(InvalidArgument);
+        }
+        if let Some(handler) = &self.component_handler {
+            handler.begin_edit(id)?;
+        }
+        if let Some(history) = self.edit_history.as_mut() {
+            history.begin_step();
+        }
+        Ok(ResOk)
+    }
+
+    /// Updates the parameter and sends its new value to the host with `perform_edit`.
+    pub fn perform_edit(
+        &mut self,
+        id: ParamId,
+        value_normalized: f64,
+    ) -> Result<ResultOk, ResultErr> {
+        let (old_value, new_value) = match self.get_parameter_mut(id) {
+            Some(param) => {
+                let old_value = param.get_normalized();
+                param.set_normalized(value_normalized);
+                (old_value, param.get_normalized())
+            }
+            None => return Err(InvalidArgument),
+        };
+        if let Some(history) = self.edit_history.as_mut() {
+            history.record(id, old_value, new_value);
+        }
+        match &self.component_handler {
+            Some(handler) => handler.perform_edit(id, new_value),
+            None => Ok(ResOk),
+        }
+    }
+
+    pub fn end_edit(&mut self, id: ParamId) -> Result<ResultOk, ResultErr> {
+        if let Some(history) = self.edit_history.as_mut() {
+            history.end_step();
+        }
+        match &self.component_handler {
+            Some(handler) => handler.end_edit(id),
+            None => Ok(ResOk),
+        }
+    }
+
+    /// Restores the values before the last edit step and sends them to the host. Nothing is
+    /// changed if the step refers to a parameter that no longer exists.
+    pub fn undo(&mut self) -> Result<ResultOk, ResultErr> {
+        let changes: Vec<(ParamId, f64)> = match self
+            .edit_history
+            .as_ref()
+            .and_then(|history| history.get_undo_step())
+        {
+            Some(step) => step
+                .get_changes()
+                .iter()
+                .rev()
+                .map(|change| (change.id, change.old_value))
+                .collect(),
+            None => return Err(ResultFalse),
+        };
+        self.replay(&changes)?;
+        if let Some(history) = self.edit_history.as_mut() {
+            history.undo_done();
+        }
+        self.send_replayed(&changes)
+    }
+
+    pub fn redo(&mut self) -> Result<ResultOk, ResultErr> {
+        let changes: Vec<(ParamId, f64)> = match self
+            .edit_history
+            .as_ref()
+            .and_then(|history| history.get_redo_step())
+        {
+            Some(step) => step
+                .get_changes()
+                .iter()
+                .map(|change| (change.id, change.new_value))
+                .collect(),
+            None => return Err(ResultFalse),
+        };
+        self.replay(&changes)?;
+        if let Some(history) = self.edit_history.as_mut() {
+            history.redo_done();
+        }
+        self.send_replayed(&changes)
+    }
+
+    fn replay(&mut self, changes: &[(ParamId, f64)]) -> Result<ResultOk, ResultErr> {
+        if changes
+            .iter()
+            .any(|(id, _)| self.get_parameter(*id).is_none())
+        {
+            return Err(InvalidArgument);
+        }
+        self.apply_values(changes);
+        Ok(ResOk)
+    }
+
+    /// Sends every change even if the host fails some of them, and returns the first error.
+    fn send_replayed(&self, changes: &[(ParamId, f64)]) -> Result<ResultOk, ResultErr> {
+        let handler = match &self.component_handler {
+            Some(handler) => handler,
+            None => return Ok(ResOk),
+        };
+        let mut result = Ok(ResOk);
+        for (id, value) in changes {
+            let edit = handler
+                .begin_edit(*id)
+                .and_then(|_| handler.perform_edit(*id, *value));
+            let end = handler.end_edit(*id);
+            if result.is_ok() {
+                result = edit.and(end);
+            }
+        }
+        result
+    }
+
     pub fn add_parameter(&mut self, p: Box<dyn Parameter>) {
-        self.id_to_index
-            .insert(p.get_info().id as usize, self.params.len());
+        self.id_to_index.insert(p.get_info().id, self.params.len());
         self.params.push(p);
     }
 
@@ -248,19 +414,104 @@ impl ParameterContainer {
     pub fn remove_all(&mut self) {
         self.params.clear();
         self.id_to_index.clear();
+        if let Some(history) = self.edit_history.as_mut() {
+            history.clear();
+        }
     }
 
-    pub fn get_parameter(&self, tag: usize) -> Option<&Box<dyn Parameter>> {
-        match self.id_to_index.get(&tag) {
+    pub fn get_parameter(&self, id: ParamId) -> Option<&Box<dyn Parameter>> {
+        match self.id_to_index.get(&id) {
             Some(index) => self.params.get(*index),
             None => None,
         }
     }
 
-    pub fn get_parameter_mut(&mut self, tag: usize) -> Option<&mut Box<dyn Parameter>> {
-        match self.id_to_index.get(&tag) {
+    pub fn get_parameter_mut(&mut self, id: ParamId) -> Option<&mut Box<dyn Parameter>> {
+        match self.id_to_index.get(&id) {
             Some(index) => self.params.get_mut(*index),
             None => None,
         }
     }
+
+    /// Writes the normalized value of every parameter that is not read-only, keyed by its ID.
+    pub fn get_state(&self, state: &Stream) -> bool {
+        let values: Vec<(ParamId, f64)> = self
+            .params
+            .iter()
+            .filter(|p| is_persistent(p.as_ref()))
+            .map(|p| (p.get_info().id, p.get_normalized()))
+            .collect();
+        write_values(state, &values)
+    }
+
+    /// Restores the values written by `get_state`. Unknown IDs are ignored and parameters missing
+    /// from the stream are reset to their default value.
+    pub fn set_state(&mut self, state: &Stream) -> bool {
+        let values = match read_values(state) {
+            Some(values) => values,
+            None => return false,
+        };
+
+        for param in self.params.iter_mut().filter(|p| is_persistent(p.as_ref())) {
+            let default_value = param.get_info().default_normalized_value;
+            param.set_normalized(default_value);
+        }
+        self.apply_values(&values);
+        if let Some(history) = self.edit_history.as_mut() {
+            history.clear();
+        }
+        true
+    }
+
+    /// Values of the parameters that make up a program: read-only and program change parameters
+    /// are left out.
+    pub fn snapshot(&self) -> Vec<(ParamId, f64)> {
+        self.params
+            .iter()
+            .filter(|p| is_persistent(p.as_ref()) && !is_program_change(p.as_ref()))
+            .map(|p| (p.get_info().id, p.get_normalized()))
+            .collect()
+    }
+
+    /// Sets the given values, skipping unknown and read-only parameters.
+    pub fn apply_values(&mut self, values: &[(ParamId, f64)]) {
+        for (id, value) in values {
+            if let Some(param) = self.get_parameter_mut(*id) {
+                if is_persistent(param.as_ref()) {
+                    param.set_normalized(*value);
+                }
+            }
+        }
+    }
+}
+
+fn is_persistent(param: &dyn Parameter) -> bool {
+    param.get_info().flags & IsReadOnly as i32 == 0
+}
+
+fn is_program_change(param: &dyn Parameter) -> bool {
+    param.get_info().flags & IsProgramChange as i32 != 0
+}
+
+pub(crate) fn write_values(state: &Stream, values: &[(ParamId, f64)]) -> bool {
+    if !state.write::<u32>(values.len() as u32) {
+        return false;
+    }
+    for (id, value) in values {
+        if !state.write::<u32>((*id).into()) || !state.write::<f64>(*value) {
+            return false;
+        }
+    }
+    true
+}
+
+pub(crate) fn read_values(state: &Stream) -> Option<Vec<(ParamId, f64)>> {
+    let count = state.read::<u32>()?;
+    let mut values = Vec::with_capacity(count.min(1024) as usize);
+    for _ in 0..count {
+        let id = ParamId(state.read::<u32>()?);
+        let value = state.read::<f64>()?;
+        values.push((id, value));
+    }
+    Some(values)
 }
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::ResultErr::{InvalidArgument, ResultFalse};
+use crate::{get_resource_path, ResultErr};
+
+/// Names of the pitches of a program, e.g. the drum map of a kit.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PitchNames {
+    names: BTreeMap<i16, String>,
+}
+
+impl PitchNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, pitch: i16, name: &str) {
+        self.names.insert(pitch, name.to_string());
+    }
+
+    pub fn get(&self, pitch: i16) -> Option<&str> {
+        self.names.get(&pitch).map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Parses one `<pitch> <name>` pair per line. Empty lines and lines starting with `#` are
+    /// skipped.
+    pub fn from_text(text: &str) -> Result<Self, ResultErr> {
+        let mut pitch_names = Self::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, char::is_whitespace);
+            let pitch = parse_pitch(parts.next().unwrap_or(""))?;
+            let name = parts.next().map(str::trim).unwrap_or("");
+            if name.is_empty() {
+                return Err(InvalidArgument);
+            }
+            pitch_names.set(pitch, name);
+        }
+        Ok(pitch_names)
+    }
+
+    /// Parses a JSON object mapping pitches to names, e.g. `{"36": "Kick", "38": "Snare"}`.
+    pub fn from_json(json: &str) -> Result<Self, ResultErr> {
+        let mut pitch_names = Self::new();
+        let mut chars = json.chars().peekable();
+        skip_whitespace(&mut chars);
+        if chars.next() != Some('{') {
+            return Err(InvalidArgument);
+        }
+        skip_whitespace(&mut chars);
+        if chars.peek() == Some(&'}') {
+            chars.next();
+        } else {
+            loop {
+                skip_whitespace(&mut chars);
+                let pitch = parse_pitch(&parse_json_string(&mut chars)?)?;
+                skip_whitespace(&mut chars);
+                if chars.next() != Some(':') {
+                    return Err(InvalidArgument);
+                }
+                skip_whitespace(&mut chars);
+                let name = parse_json_string(&mut chars)?;
+                pitch_names.set(pitch, &name);
+                skip_whitespace(&mut chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => break,
+                    _ => return Err(InvalidArgument),
+                }
+            }
+        }
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(pitch_names),
+            Some(_) => Err(InvalidArgument),
+        }
+    }
+
+    /// Loads a resource with `from_json` if the file has a `json` extension and with `from_text`
+    /// otherwise.
+    pub fn load(path: &Path) -> Result<Self, ResultErr> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                log::error!("Could not read pitch names from {}: {}", path.display(), e);
+                return Err(ResultFalse);
+            }
+        };
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::from_json(&contents),
+            _ => Self::from_text(&contents),
+        }
+    }
+
+    /// Loads a file of the bundle resources with `load`, e.g. `load_resource("drums.json")`.
+    pub fn load_resource(name: &str) -> Result<Self, ResultErr> {
+        match get_resource_path(name) {
+            Some(path) => Self::load(&path),
+            None => {
+                log::error!("Could not find the bundle resources for {}", name);
+                Err(ResultFalse)
+            }
+        }
+    }
+}
+
+fn parse_pitch(pitch: &str) -> Result<i16, ResultErr> {
+    match pitch.trim().parse::<i16>() {
+        Ok(pitch) if (0..128).contains(&pitch) => Ok(pitch),
+        _ => Err(InvalidArgument),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_json_string(chars: &mut Peekable<Chars>) -> Result<String, ResultErr> {
+    if chars.next() != Some('"') {
+        return Err(InvalidArgument);
+    }
+    let mut string = String::new();
+    loop {
+        match chars.next().ok_or(InvalidArgument)? {
+            '"' => return Ok(string),
+            '\\' => {
+                let c = match chars.next().ok_or(InvalidArgument)? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => parse_json_unicode(chars)?,
+                    c @ '"' | c @ '\\' | c @ '/' => c,
+                    _ => return Err(InvalidArgument),
+                };
+                string.push(c);
+            }
+            c => string.push(c),
+        }
+    }
+}
+
+/// Reads the digits after `\u`, and the low surrogate escape following a high one.
+fn parse_json_unicode(chars: &mut Peekable<Chars>) -> Result<char, ResultErr> {
+    let high = parse_hex4(chars)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return Ok(std::char::from_u32(high).unwrap_or(std::char::REPLACEMENT_CHARACTER));
+    }
+    if chars.next() != Some('\\') || chars.next() != Some('u') {
+        return Err(InvalidArgument);
+    }
+    let low = parse_hex4(chars)?;
+    if !(0xDC00..0xE000).contains(&low) {
+        return Err(InvalidArgument);
+    }
+    let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+    std::char::from_u32(code).ok_or(InvalidArgument)
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, ResultErr> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = chars.next().and_then(|c| c.to_digit(16));
+        code = code * 16 + digit.ok_or(InvalidArgument)?;
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PitchNames;
+
+    #[test]
+    fn test_from_text() {
+        let names = PitchNames::from_text("# GM kit\n36 Kick\n\n38  Acoustic Snare\n").unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.get(36), Some("Kick"));
+        assert_eq!(names.get(38), Some("Acoustic Snare"));
+        assert!(PitchNames::from_text("128 Out of range").is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let names = PitchNames::from_json(
+            r#"{ "36": "Kick", "42": "Hi-Hat \"Closed\"", "49": "Crash \ud83e\udd41" }"#,
+        )
+        .unwrap();
+        assert_eq!(names.get(36), Some("Kick"));
+        assert_eq!(names.get(42), Some("Hi-Hat \"Closed\""));
+        assert_eq!(names.get(49), Some("Crash \u{1F941}"));
+        assert!(PitchNames::from_json(r#"{"36": "\ud83e"}"#).is_err());
+        assert!(PitchNames::from_json(r#"{"36": "Kick",}"#).is_err());
+    }
+}
@@ -8,8 +8,9 @@ use vst3_sys::VST3;
 
 use crate::ResultErr::InvalidArgument;
 use crate::{
-    AudioProcessor, ClassInfo, Component, EditController, HostApplication, MidiMapping, ResultErr,
-    ResultOk, UnitInfo, Unknown,
+    AudioProcessor, ClassInfo, Component, EditController, HostApplication, KeyswitchController,
+    MidiLearn, MidiMapping, NoteExpressionController, ProcessContextRequirements, ProgramListData,
+    ResultErr, ResultOk, UnitInfo, Unknown,
 };
 
 pub trait PluginBase {
@@ -29,12 +30,27 @@ pub trait PluginBase {
     fn as_audio_processor(&mut self) -> Option<&mut dyn AudioProcessor> {
         None
     }
+    fn as_process_context_requirements(&mut self) -> Option<&mut dyn ProcessContextRequirements> {
+        None
+    }
     fn as_unit_info(&mut self) -> Option<&mut dyn UnitInfo> {
         None
     }
     fn as_midi_mapping(&mut self) -> Option<&mut dyn MidiMapping> {
         None
     }
+    fn as_midi_learn(&mut self) -> Option<&mut dyn MidiLearn> {
+        None
+    }
+    fn as_program_list_data(&mut self) -> Option<&mut dyn ProgramListData> {
+        None
+    }
+    fn as_note_expression_controller(&mut self) -> Option<&mut dyn NoteExpressionController> {
+        None
+    }
+    fn as_keyswitch_controller(&mut self) -> Option<&mut dyn KeyswitchController> {
+        None
+    }
 
     fn initialize(&mut self, context: HostApplication) -> bool;
     fn terminate(&mut self) -> bool;
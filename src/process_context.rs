@@ -0,0 +1,158 @@
+use std::sync::Mutex;
+
+use vst3_sys::vst::IProcessContextRequirements;
+
+use crate::{AudioProcessor, VST3Component};
+
+const PLAYING: u32 = 1 << 1;
+const CYCLE_ACTIVE: u32 = 1 << 2;
+const RECORDING: u32 = 1 << 3;
+const SYSTEM_TIME_VALID: u32 = 1 << 8;
+const PROJECT_TIME_MUSIC_VALID: u32 = 1 << 9;
+const TEMPO_VALID: u32 = 1 << 10;
+const BAR_POSITION_VALID: u32 = 1 << 11;
+const CYCLE_VALID: u32 = 1 << 12;
+const TIME_SIG_VALID: u32 = 1 << 13;
+const SMPTE_VALID: u32 = 1 << 14;
+const CLOCK_VALID: u32 = 1 << 15;
+const CONT_TIME_VALID: u32 = 1 << 17;
+const CHORD_VALID: u32 = 1 << 18;
+
+const FRAME_RATE_PULL_DOWN: u32 = 1;
+const FRAME_RATE_DROP: u32 = 1 << 1;
+
+pub enum ProcessContextRequirement {
+    NeedSystemTime = 1,
+    NeedContinousTimeSamples = 1 << 1,
+    NeedProjectTimeMusic = 1 << 2,
+    NeedBarPositionMusic = 1 << 3,
+    NeedCycleMusic = 1 << 4,
+    NeedSamplesToNextClock = 1 << 5,
+    NeedTempo = 1 << 6,
+    NeedTimeSignature = 1 << 7,
+    NeedChord = 1 << 8,
+    NeedFrameRate = 1 << 9,
+    NeedTransportState = 1 << 10,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeSignature {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ContextChord {
+    pub key_note: u8,
+    pub root_note: u8,
+    /// Bit 0 is the root note, bit 11 the major seventh.
+    pub chord_mask: i16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameRate {
+    pub frames_per_second: u32,
+    pub pull_down: bool,
+    pub drop_frame: bool,
+}
+
+/// Transport state and musical position at the start of the block. Fields the host did not
+/// mark as valid are `None`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProcessContext {
+    pub sample_rate: f64,
+    pub project_time_samples: i64,
+    pub playing: bool,
+    pub cycle_active: bool,
+    pub recording: bool,
+    /// In nanoseconds.
+    pub system_time: Option<i64>,
+    pub continuous_time_samples: Option<i64>,
+    /// In quarter notes.
+    pub project_time_music: Option<f64>,
+    /// Position of the last bar start in quarter notes.
+    pub bar_position_music: Option<f64>,
+    /// Start and end of the cycle in quarter notes.
+    pub cycle_music: Option<(f64, f64)>,
+    pub tempo: Option<f64>,
+    pub time_signature: Option<TimeSignature>,
+    pub chord: Option<ContextChord>,
+    /// SMPTE offset in subframes and the frame rate.
+    pub smpte: Option<(i32, FrameRate)>,
+    pub samples_to_next_clock: Option<i32>,
+}
+
+impl From<&vst3_sys::vst::ProcessContext> for ProcessContext {
+    fn from(context: &vst3_sys::vst::ProcessContext) -> Self {
+        let state = context.state;
+        let valid = |flag: u32| state & flag != 0;
+        ProcessContext {
+            sample_rate: context.sample_rate,
+            project_time_samples: context.project_time_samples,
+            playing: valid(PLAYING),
+            cycle_active: valid(CYCLE_ACTIVE),
+            recording: valid(RECORDING),
+            system_time: Some(context.system_time).filter(|_| valid(SYSTEM_TIME_VALID)),
+            continuous_time_samples: Some(context.continuous_time_samples)
+                .filter(|_| valid(CONT_TIME_VALID)),
+            project_time_music: Some(context.project_time_music)
+                .filter(|_| valid(PROJECT_TIME_MUSIC_VALID)),
+            bar_position_music: Some(context.bar_position_music)
+                .filter(|_| valid(BAR_POSITION_VALID)),
+            cycle_music: Some((context.cycle_start_music, context.cycle_end_music))
+                .filter(|_| valid(CYCLE_VALID)),
+            tempo: Some(context.tempo).filter(|_| valid(TEMPO_VALID)),
+            time_signature: Some(TimeSignature {
+                numerator: context.time_sig_num,
+                denominator: context.time_sig_den,
+            })
+            .filter(|_| valid(TIME_SIG_VALID)),
+            chord: Some(ContextChord {
+                key_note: context.chord.key_note,
+                root_note: context.chord.root_note,
+                chord_mask: context.chord.chord_mask,
+            })
+            .filter(|_| valid(CHORD_VALID)),
+            smpte: Some((
+                context.smpte_offset_subframes,
+                FrameRate {
+                    frames_per_second: context.frame_rate.frames_per_second,
+                    pull_down: context.frame_rate.flags & FRAME_RATE_PULL_DOWN != 0,
+                    drop_frame: context.frame_rate.flags & FRAME_RATE_DROP != 0,
+                },
+            ))
+            .filter(|_| valid(SMPTE_VALID)),
+            samples_to_next_clock: Some(context.samples_to_next_clock)
+                .filter(|_| valid(CLOCK_VALID)),
+        }
+    }
+}
+
+pub trait ProcessContextRequirements: AudioProcessor {
+    /// Combination of `ProcessContextRequirement` flags.
+    fn get_process_context_requirements(&self) -> u32;
+}
+
+/// Hosts fill every field for plug-ins that do not declare their requirements.
+const ALL_REQUIREMENTS: u32 = (1 << 11) - 1;
+
+impl IProcessContextRequirements for VST3Component {
+    unsafe fn get_process_context_requirements(&self) -> u32 {
+        let mutex_plugin_base = self.get_plugin_base();
+        let ret: Mutex<u32> = Mutex::new(ALL_REQUIREMENTS);
+        match std::panic::catch_unwind(|| {
+            if let Ok(mut plugin_base) = mutex_plugin_base.lock() {
+                if let Some(requirements) = plugin_base.as_process_context_requirements() {
+                    *ret.lock().unwrap() = requirements.get_process_context_requirements();
+                }
+            }
+        }) {
+            Ok(_) => *ret.lock().unwrap(),
+            Err(_) => {
+                #[cfg(debug_assertions)]
+                log::error!("VST3Component: get_process_context_requirements: panic");
+                *ret.lock().unwrap()
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::os::raw::c_void;
+use std::ptr;
+
+use num_traits::Float;
+
+use vst3_sys::vst::AudioBusBuffers;
+
+use crate::ResultOk::ResOk;
+use crate::{
+    Event, EventList, MidiBlock, ProcessContext, ProcessData, ProcessMode, ResultErr, ResultOk,
+};
+
+/// Buffers and events to call `process` without a host, e.g. from tests or an offline renderer.
+/// Input parameter changes are not supported.
+pub struct ProcessDataBuilder<T: Float> {
+    process_mode: ProcessMode,
+    num_samples: usize,
+    inputs: Vec<Vec<Vec<T>>>,
+    outputs: Vec<Vec<Vec<T>>>,
+    in_events: EventList,
+    process_context: Option<ProcessContext>,
+    channel_pointers: Vec<Vec<*mut c_void>>,
+    buses: Vec<AudioBusBuffers>,
+}
+
+impl<T: Float> ProcessDataBuilder<T> {
+    pub fn new(num_samples: usize) -> Self {
+        Self {
+            process_mode: ProcessMode::Offline,
+            num_samples,
+            inputs: vec![],
+            outputs: vec![],
+            in_events: EventList::new(),
+            process_context: None,
+            channel_pointers: vec![],
+            buses: vec![],
+        }
+    }
+
+    /// Adds an input bus of silent channels.
+    pub fn input_bus(mut self, num_channels: usize) -> Self {
+        self.inputs
+            .push(vec![vec![T::zero(); self.num_samples]; num_channels]);
+        self
+    }
+
+    pub fn output_bus(mut self, num_channels: usize) -> Self {
+        self.outputs
+            .push(vec![vec![T::zero(); self.num_samples]; num_channels]);
+        self
+    }
+
+    pub fn process_mode(mut self, process_mode: ProcessMode) -> Self {
+        self.process_mode = process_mode;
+        self
+    }
+
+    pub fn process_context(mut self, process_context: ProcessContext) -> Self {
+        self.process_context = Some(process_context);
+        self
+    }
+
+    pub fn get_input_mut(&mut self, bus: usize, channel: usize) -> Option<&mut [T]> {
+        self.inputs
+            .get_mut(bus)?
+            .get_mut(channel)
+            .map(|channel| channel.as_mut_slice())
+    }
+
+    /// The samples written by the last processed block.
+    pub fn get_output(&self, bus: usize, channel: usize) -> Option<&[T]> {
+        self.outputs
+            .get(bus)?
+            .get(channel)
+            .map(|channel| channel.as_slice())
+    }
+
+    /// Adds an input event to the next block.
+    pub fn add_event(&mut self, event: &Event) -> Result<ResultOk, ResultErr> {
+        self.in_events.add_copied_event(event)
+    }
+
+    pub fn add_midi_block(
+        &mut self,
+        block: &MidiBlock,
+        bus_index: i32,
+    ) -> Result<ResultOk, ResultErr> {
+        for event in block.events(bus_index) {
+            self.add_event(&event)?;
+        }
+        Ok(ResOk)
+    }
+
+    /// The next block, with the input events added since the previous one and an empty list
+    /// for the output events.
+    pub fn build(&mut self) -> ProcessData<'_, T> {
+        self.channel_pointers.clear();
+        for bus in self.inputs.iter_mut().chain(self.outputs.iter_mut()) {
+            self.channel_pointers.push(
+                bus.iter_mut()
+                    .map(|channel| channel.as_mut_ptr() as *mut c_void)
+                    .collect(),
+            );
+        }
+        self.buses = self
+            .channel_pointers
+            .iter_mut()
+            .map(|pointers| AudioBusBuffers {
+                num_channels: pointers.len() as i32,
+                silence_flags: 0,
+                buffers: if pointers.is_empty() {
+                    ptr::null_mut()
+                } else {
+                    pointers.as_mut_ptr()
+                },
+            })
+            .collect();
+
+        let in_events = std::mem::take(&mut self.in_events);
+        let (inputs, outputs) = self.buses.split_at_mut(self.inputs.len());
+        ProcessData::from_parts(
+            inputs,
+            outputs,
+            self.process_mode,
+            self.num_samples,
+            Some(Box::new(in_events)),
+            Some(Box::new(EventList::new())),
+            self.process_context,
+        )
+    }
+}
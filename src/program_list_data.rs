@@ -0,0 +1,97 @@
+use std::os::raw::c_void;
+use vst3_sys::vst::IProgramListData;
+
+use crate::parameters::{read_values, write_values};
+use crate::unit_info::{get_program, set_program_values};
+use crate::ResultErr::{InvalidArgument, NotImplemented, ResultFalse};
+use crate::ResultOk::ResOk;
+use crate::{ResultErr, ResultOk, Stream, UnitInfo, Unknown, VST3EditController};
+
+pub trait ProgramListData: UnitInfo {
+    fn program_data_supported(&self, list_id: i32) -> Result<ResultOk, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        match tree.get_program_list(list_id) {
+            Some(_) => Ok(ResOk),
+            None => Err(ResultFalse),
+        }
+    }
+
+    /// Writes the program values in the format written by `ParameterContainer::get_state`.
+    fn get_program_data(
+        &self,
+        list_id: i32,
+        program_index: i32,
+        data: &Stream,
+    ) -> Result<ResultOk, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let program = get_program(tree, list_id, program_index).ok_or(InvalidArgument)?;
+        if write_values(data, &program.values) {
+            Ok(ResOk)
+        } else {
+            Err(ResultFalse)
+        }
+    }
+
+    fn set_program_data(
+        &mut self,
+        list_id: i32,
+        program_index: i32,
+        data: &Stream,
+    ) -> Result<ResultOk, ResultErr> {
+        let values = read_values(data).ok_or(ResultFalse)?;
+        set_program_values(self, list_id, program_index, values)
+    }
+}
+
+impl IProgramListData for VST3EditController {
+    unsafe fn program_data_supported(&self, list_id: i32) -> i32 {
+        if let Some(program_list_data) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_program_list_data()
+        {
+            return match program_list_data.program_data_supported(list_id) {
+                Ok(r) => r.into(),
+                Err(r) => r.into(),
+            };
+        }
+        NotImplemented.into()
+    }
+
+    unsafe fn get_program_data(&self, list_id: i32, program_index: i32, data: *mut c_void) -> i32 {
+        if let Some(program_list_data) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_program_list_data()
+        {
+            if let Some(data) = Stream::from_raw(data) {
+                return match program_list_data.get_program_data(list_id, program_index, &data) {
+                    Ok(r) => r.into(),
+                    Err(r) => r.into(),
+                };
+            }
+            return InvalidArgument.into();
+        }
+        NotImplemented.into()
+    }
+
+    unsafe fn set_program_data(&self, list_id: i32, program_index: i32, data: *mut c_void) -> i32 {
+        if let Some(program_list_data) = self
+            .get_plugin_base()
+            .lock()
+            .unwrap()
+            .as_program_list_data()
+        {
+            if let Some(data) = Stream::from_raw(data) {
+                return match program_list_data.set_program_data(list_id, program_index, &data) {
+                    Ok(r) => r.into(),
+                    Err(r) => r.into(),
+                };
+            }
+            return InvalidArgument.into();
+        }
+        NotImplemented.into()
+    }
+}
@@ -0,0 +1,221 @@
+use std::os::raw::c_void;
+use std::ptr::null_mut;
+use std::slice;
+
+use vst3_sys::vst::AudioBusBuffers;
+
+use crate::{BusDirection, Component, MediaType, ProcessData};
+
+struct BusScratch {
+    samples: Vec<Vec<f32>>,
+    pointers: Vec<*mut f32>,
+}
+
+impl BusScratch {
+    fn new(num_channels: usize, max_samples: usize) -> Self {
+        let mut samples: Vec<Vec<f32>> =
+            (0..num_channels).map(|_| vec![0.0; max_samples]).collect();
+        let pointers = samples.iter_mut().map(|s| s.as_mut_ptr()).collect();
+        Self { samples, pointers }
+    }
+}
+
+/// f32 buffers standing in for the f64 buffers of the host, for `process_f64_as_f32`. Everything
+/// is allocated up front in `setup_processing`, and again in `set_active` since the bus
+/// arrangements may have changed in between.
+pub(crate) struct SampleConverter {
+    max_samples: usize,
+    inputs: Vec<BusScratch>,
+    outputs: Vec<BusScratch>,
+    input_buffers: Vec<AudioBusBuffers>,
+    output_buffers: Vec<AudioBusBuffers>,
+}
+
+impl SampleConverter {
+    pub(crate) fn new(
+        max_samples: usize,
+        input_channels: &[usize],
+        output_channels: &[usize],
+    ) -> Self {
+        let empty = AudioBusBuffers {
+            num_channels: 0,
+            silence_flags: 0,
+            buffers: null_mut(),
+        };
+        Self {
+            max_samples,
+            inputs: input_channels
+                .iter()
+                .map(|c| BusScratch::new(*c, max_samples))
+                .collect(),
+            outputs: output_channels
+                .iter()
+                .map(|c| BusScratch::new(*c, max_samples))
+                .collect(),
+            input_buffers: vec![empty; input_channels.len()],
+            output_buffers: vec![empty; output_channels.len()],
+        }
+    }
+
+    /// Keeps the block size, reallocating the buffers for the current channel counts.
+    pub(crate) fn set_channels(&mut self, input_channels: &[usize], output_channels: &[usize]) {
+        *self = Self::new(self.max_samples, input_channels, output_channels);
+    }
+
+    /// Converts the inputs, calls `process` with the f32 buffers and converts the outputs back.
+    /// Returns `false` without processing if the block does not fit the scratch buffers.
+    pub(crate) unsafe fn process<F>(
+        &mut self,
+        data: &vst3_sys::vst::ProcessData,
+        process: F,
+    ) -> bool
+    where
+        F: FnOnce(&mut ProcessData<f32>),
+    {
+        let num_samples = data.num_samples.max(0) as usize;
+        let host_inputs = host_buses(data.inputs, data.num_inputs);
+        let host_outputs = host_buses(data.outputs, data.num_outputs);
+        if num_samples > self.max_samples
+            || !fits(host_inputs, &self.inputs)
+            || !fits(host_outputs, &self.outputs)
+        {
+            log::error!("process: block does not fit the buffers prepared in setup_processing");
+            return false;
+        }
+
+        for (index, host) in host_inputs.iter().enumerate() {
+            let scratch = &mut self.inputs[index];
+            for (channel, samples) in channels(host).iter().zip(scratch.samples.iter_mut()) {
+                if channel.is_null() || num_samples == 0 {
+                    continue;
+                }
+                let channel = slice::from_raw_parts(*channel as *const f64, num_samples);
+                for (sample, host_sample) in samples.iter_mut().zip(channel) {
+                    *sample = *host_sample as f32;
+                }
+            }
+            self.input_buffers[index] = AudioBusBuffers {
+                num_channels: host.num_channels,
+                silence_flags: host.silence_flags,
+                buffers: scratch.pointers.as_mut_ptr() as *mut *mut c_void,
+            };
+        }
+        for (index, host) in host_outputs.iter().enumerate() {
+            self.output_buffers[index] = AudioBusBuffers {
+                num_channels: host.num_channels,
+                silence_flags: host.silence_flags,
+                buffers: self.outputs[index].pointers.as_mut_ptr() as *mut *mut c_void,
+            };
+        }
+
+        let mut process_data = ProcessData::<f32>::with_buses(
+            data,
+            &self.input_buffers[..host_inputs.len()],
+            &mut self.output_buffers[..host_outputs.len()],
+        );
+        process(&mut process_data);
+
+        for (index, host) in host_outputs.iter_mut().enumerate() {
+            for (channel, samples) in channels(host)
+                .iter()
+                .zip(self.outputs[index].samples.iter())
+            {
+                if channel.is_null() || num_samples == 0 {
+                    continue;
+                }
+                let channel = slice::from_raw_parts_mut(*channel, num_samples);
+                for (host_sample, sample) in channel.iter_mut().zip(samples.iter()) {
+                    *host_sample = *sample as f64;
+                }
+            }
+            host.silence_flags = self.output_buffers[index].silence_flags;
+        }
+        true
+    }
+}
+
+unsafe fn host_buses<'a>(buffers: *mut AudioBusBuffers, count: i32) -> &'a mut [AudioBusBuffers] {
+    if buffers.is_null() || count <= 0 {
+        return &mut [];
+    }
+    slice::from_raw_parts_mut(buffers, count as usize)
+}
+
+unsafe fn channels<'a>(bus: &AudioBusBuffers) -> &'a [*mut f64] {
+    if bus.buffers.is_null() || bus.num_channels <= 0 {
+        return &[];
+    }
+    slice::from_raw_parts(bus.buffers as *const *mut f64, bus.num_channels as usize)
+}
+
+/// Channel count of every audio bus in `dir`.
+pub(crate) fn bus_channels<C: Component + ?Sized>(component: &C, dir: BusDirection) -> Vec<usize> {
+    (0..component.get_bus_count(&MediaType::Audio, &dir))
+        .map(|index| {
+            component
+                .get_bus_info(&MediaType::Audio, &dir, index)
+                .map_or(0, |info| info.channel_count.max(0) as usize)
+        })
+        .collect()
+}
+
+fn fits(host: &[AudioBusBuffers], scratch: &[BusScratch]) -> bool {
+    host.len() <= scratch.len()
+        && host.iter().zip(scratch.iter()).all(|(bus, scratch)| {
+            bus.num_channels >= 0 && bus.num_channels as usize <= scratch.samples.len()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::raw::c_void;
+    use std::ptr::null_mut;
+
+    use vst3_sys::vst::AudioBusBuffers;
+
+    use super::SampleConverter;
+
+    fn bus(pointers: &mut [*mut f64]) -> AudioBusBuffers {
+        AudioBusBuffers {
+            num_channels: pointers.len() as i32,
+            silence_flags: 0,
+            buffers: pointers.as_mut_ptr() as *mut *mut c_void,
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        let mut input = [0.5, -0.25, 1.0];
+        let mut output = [0.0; 3];
+        let mut input_pointers = [input.as_mut_ptr()];
+        // The second output channel has no buffer.
+        let mut output_pointers = [output.as_mut_ptr(), null_mut()];
+        let mut inputs = [bus(&mut input_pointers)];
+        let mut outputs = [bus(&mut output_pointers)];
+        let mut data: vst3_sys::vst::ProcessData = unsafe { std::mem::zeroed() };
+        data.num_samples = 3;
+        data.num_inputs = 1;
+        data.num_outputs = 1;
+        data.inputs = inputs.as_mut_ptr();
+        data.outputs = outputs.as_mut_ptr();
+
+        let mut converter = SampleConverter::new(4, &[1], &[2]);
+        let processed = unsafe {
+            converter.process(&data, |data| {
+                let (inputs, mut outputs) = data.split_buffers();
+                let input = inputs.get(0).unwrap();
+                let mut output = outputs.get_mut(0).unwrap();
+                for pair in output.zip(&input) {
+                    pair.map(|sample| sample * 2.0);
+                }
+            })
+        };
+        assert!(processed);
+        assert_eq!(output, [1.0, -0.5, 2.0]);
+
+        data.num_samples = 0;
+        assert!(unsafe { converter.process(&data, |_| ()) });
+        data.num_samples = 5;
+        assert!(!unsafe { converter.process(&data, |_| ()) });
+    }
+}
@@ -52,7 +52,9 @@ impl Stream {
                 std::mem::size_of::<T>() as i32,
                 &mut num_bytes_read,
             ) {
-                r if r == ResOk.into() => return Some(saved_value),
+                r if r == ResOk.into() && num_bytes_read == std::mem::size_of::<T>() as i32 => {
+                    return Some(saved_value)
+                }
                 r => None,
             }
         }
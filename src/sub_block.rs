@@ -0,0 +1,186 @@
+use std::marker::PhantomData;
+
+use vst3_sys::vst::AudioBusBuffers;
+
+use crate::{Event, EventList, Inputs, Outputs, ParamId, ParameterChanges};
+
+/// Capacity of the buffer the sub-block boundaries are sorted in. It only grows, off the usual
+/// path, for blocks with more events and parameter points.
+pub(crate) const SUB_BLOCK_POINTS_CAPACITY: usize = 1024;
+
+/// An input event or parameter point, read once per block.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct SubBlockPoint {
+    offset: usize,
+    kind: PointKind,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PointKind {
+    Event(i32),
+    ParamChange {
+        queue: usize,
+        point: i32,
+        id: ParamId,
+        value: f64,
+    },
+}
+
+/// Fills `points` with the events and parameter points of the block, sorted by sample offset.
+/// Within an offset, events come first and keep their order, as do the points of a queue.
+pub(crate) fn collect_points(
+    points: &mut Vec<SubBlockPoint>,
+    num_samples: usize,
+    events: Option<&EventList>,
+    param_changes: Option<&ParameterChanges>,
+) {
+    points.clear();
+    if let Some(events) = events {
+        for index in 0..events.get_event_count() {
+            if let Ok(event) = events.get_event(index) {
+                points.push(SubBlockPoint {
+                    offset: clamp_offset(event.get_header().sample_offset, num_samples),
+                    kind: PointKind::Event(index),
+                });
+            }
+        }
+    }
+    if let Some(changes) = param_changes {
+        for queue_index in 0..changes.get_parameter_count() {
+            if let Some(queue) = changes.get_parameter_data(queue_index) {
+                let id = queue.get_parameter_id();
+                for point_index in 0..queue.get_point_count() {
+                    if let Ok(point) = queue.get_point(point_index) {
+                        points.push(SubBlockPoint {
+                            offset: clamp_offset(point.sample_offset, num_samples),
+                            kind: PointKind::ParamChange {
+                                queue: queue_index,
+                                point: point_index,
+                                id,
+                                value: point.value,
+                            },
+                        });
+                    }
+                }
+            }
+        }
+    }
+    // Unstable sorting does not allocate, the key is unique.
+    points.sort_unstable_by_key(|point| match point.kind {
+        PointKind::Event(index) => (point.offset, 0, 0, index),
+        PointKind::ParamChange {
+            queue,
+            point: index,
+            ..
+        } => (point.offset, 1, queue, index),
+    });
+}
+
+/// Part of a block in which no event or parameter change happens after `start`. Sub-blocks
+/// cover disjoint samples, so their outputs can be held at the same time.
+pub struct SubBlock<'a, T: 'a> {
+    pub start: usize,
+    pub len: usize,
+    pub inputs: Inputs<'a, T>,
+    pub outputs: Outputs<'a, T>,
+    events: Option<&'a EventList>,
+    points: &'a [SubBlockPoint],
+}
+
+impl<'a, T> SubBlock<'a, T> {
+    /// Events to handle before processing the sub-block.
+    pub fn events(&self) -> impl Iterator<Item = Event<'a>> + '_ {
+        let events = self.events;
+        self.points
+            .iter()
+            .filter_map(move |point| match point.kind {
+                PointKind::Event(index) => events?.get_event(index).ok(),
+                PointKind::ParamChange { .. } => None,
+            })
+    }
+
+    /// Parameter values to apply before processing the sub-block.
+    pub fn param_changes(&self) -> impl Iterator<Item = (ParamId, f64)> + '_ {
+        self.points.iter().filter_map(|point| match point.kind {
+            PointKind::ParamChange { id, value, .. } => Some((id, value)),
+            PointKind::Event(_) => None,
+        })
+    }
+}
+
+pub struct SubBlocks<'a, T: 'a> {
+    inputs: &'a [AudioBusBuffers],
+    outputs: *mut AudioBusBuffers,
+    num_outputs: usize,
+    num_samples: usize,
+    events: Option<&'a EventList>,
+    points: &'a [SubBlockPoint],
+    cursor: usize,
+    start: usize,
+    finished: bool,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> SubBlocks<'a, T> {
+    /// `points` are the ones filled by `collect_points` for the block.
+    pub(crate) fn new(
+        inputs: &'a [AudioBusBuffers],
+        outputs: *mut AudioBusBuffers,
+        num_outputs: usize,
+        num_samples: usize,
+        events: Option<&'a EventList>,
+        points: &'a [SubBlockPoint],
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            num_outputs,
+            num_samples,
+            events,
+            points,
+            cursor: 0,
+            start: 0,
+            finished: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for SubBlocks<'a, T> {
+    type Item = SubBlock<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        // An empty block still yields one sub-block so that its events and changes are handled.
+        let start = self.start;
+        let first = self.cursor;
+        while self.cursor < self.points.len() && self.points[self.cursor].offset <= start {
+            self.cursor += 1;
+        }
+        let end = match self.points.get(self.cursor) {
+            Some(point) => point.offset,
+            None => self.num_samples,
+        };
+        let len = end - start;
+        self.start = end;
+        self.finished = end >= self.num_samples;
+        Some(SubBlock {
+            start,
+            len,
+            inputs: Inputs::new(self.inputs, self.outputs, self.num_outputs, start, len),
+            outputs: Outputs::new(self.outputs, self.num_outputs, start, len, self.num_samples),
+            events: self.events,
+            points: &self.points[first..self.cursor],
+        })
+    }
+}
+
+/// Offsets outside of the block are moved to its first or last sample.
+fn clamp_offset(sample_offset: i32, num_samples: usize) -> usize {
+    if sample_offset <= 0 || num_samples == 0 {
+        return 0;
+    }
+    (sample_offset as usize).min(num_samples - 1)
+}
@@ -1,12 +1,21 @@
+use std::collections::HashMap;
+
 use vst3_sys::vst::{kNoParentUnitId, kNoProgramListId, kRootUnitId};
 
-use crate::wstrcpy;
+use crate::ParameterFlag::{CanAutomate, IsList, IsProgramChange};
+use crate::ResultErr::InvalidArgument;
+use crate::ResultOk::ResOk;
+use crate::{
+    wstrcpy, BaseParameter, BusDirection, MediaType, ParamId, Parameter, ParameterContainer,
+    ParameterInfo, ParameterInfoBuilder, PitchNames, ResultErr, ResultOk,
+};
 
 pub const ROOT_UNIT_ID: i32 = kRootUnitId;
 pub const NO_PARENT_UNIT_ID: i32 = kNoParentUnitId;
 
 pub const NO_PROGRAM_LIST_ID: i32 = kNoProgramListId;
 
+#[derive(Clone)]
 pub struct Unit {
     pub id: i32,
     pub parent_unit_id: i32,
@@ -64,26 +73,41 @@ impl UnitBuilder {
     }
 }
 
-pub struct ProgramList {
-    pub id: i32,
+#[derive(Clone)]
+pub struct Program {
     pub name: String,
-    pub program_count: i32,
+    pub values: Vec<(ParamId, f64)>,
+    pub attributes: HashMap<String, String>,
+    /// Reported through `UnitInfo::get_program_pitch_name` when not empty.
+    pub pitch_names: PitchNames,
 }
 
-impl ProgramList {
-    pub fn new(id: i32, name: &str, program_count: i32) -> Self {
+impl Program {
+    pub fn new(name: &str, values: Vec<(ParamId, f64)>) -> Self {
         Self {
-            id,
             name: name.to_string(),
-            program_count,
+            values,
+            attributes: HashMap::new(),
+            pitch_names: PitchNames::new(),
         }
     }
+}
+
+#[derive(Clone)]
+pub struct ProgramList {
+    pub id: i32,
+    pub name: String,
+    pub unit_id: i32,
+    pub parameter_id: ParamId,
+    programs: Vec<Program>,
+}
 
+impl ProgramList {
     pub fn get_info(&self) -> vst3_sys::vst::ProgramListInfo {
         let mut info = vst3_sys::vst::ProgramListInfo {
             id: self.id,
             name: [0; 128],
-            program_count: self.program_count,
+            program_count: self.programs.len() as i32,
         };
         unsafe {
             wstrcpy(&self.name, info.name.as_mut_ptr() as *mut i16);
@@ -91,4 +115,421 @@ impl ProgramList {
 
         info
     }
+
+    pub fn get_program_count(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn get_program(&self, index: usize) -> Option<&Program> {
+        self.programs.get(index)
+    }
+
+    pub fn get_program_mut(&mut self, index: usize) -> Option<&mut Program> {
+        self.programs.get_mut(index)
+    }
+
+    /// Index of the program selected by the normalized value of the program change parameter.
+    pub fn get_program_index(&self, value_normalized: f64) -> usize {
+        program_index(self.programs.len(), value_normalized)
+    }
+}
+
+pub struct ProgramListBuilder {
+    id: i32,
+    name: String,
+    unit_id: i32,
+    parameter_id: ParamId,
+    programs: Vec<Program>,
+}
+
+impl ProgramListBuilder {
+    pub fn new(name: &str, id: i32, parameter_id: ParamId) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            unit_id: ROOT_UNIT_ID,
+            parameter_id,
+            programs: vec![],
+        }
+    }
+
+    pub fn unit_id(mut self, unit_id: i32) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    pub fn program(mut self, program: Program) -> Self {
+        self.programs.push(program);
+        self
+    }
+
+    pub fn build(self) -> ProgramList {
+        ProgramList {
+            id: self.id,
+            name: self.name,
+            unit_id: self.unit_id,
+            parameter_id: self.parameter_id,
+            programs: self.programs,
+        }
+    }
+}
+
+fn program_index(program_count: usize, value_normalized: f64) -> usize {
+    let last = program_count.saturating_sub(1);
+    let index = (value_normalized.clamp(0.0, 1.0) * last as f64).round() as usize;
+    index.min(last)
+}
+
+/// List parameter created for every program list added to a `UnitTree`. Its value selects the
+/// program of the list.
+pub struct ProgramChangeParameter {
+    base: BaseParameter,
+    program_names: Vec<String>,
+}
+
+impl ProgramChangeParameter {
+    pub fn new(program_list: &ProgramList) -> Box<Self> {
+        // A single program still gets a step, so that the parameter is not continuous.
+        let info = ParameterInfoBuilder::new(&program_list.name, program_list.parameter_id)
+            .step_count(program_list.get_program_count().saturating_sub(1).max(1) as i32)
+            .unit_id(program_list.unit_id)
+            .flags(CanAutomate as i32 | IsList as i32 | IsProgramChange as i32)
+            .build();
+        Box::new(Self {
+            base: *BaseParameter::new(info),
+            program_names: program_list
+                .programs
+                .iter()
+                .map(|program| program.name.clone())
+                .collect(),
+        })
+    }
+}
+
+impl Parameter for ProgramChangeParameter {
+    fn get_info(&self) -> &ParameterInfo {
+        self.base.get_info()
+    }
+
+    fn get_info_mut(&mut self) -> &mut ParameterInfo {
+        self.base.get_info_mut()
+    }
+
+    fn set_unit_id(&mut self, id: i32) {
+        self.base.set_unit_id(id)
+    }
+
+    fn get_unit_id(&self) -> i32 {
+        self.base.get_unit_id()
+    }
+
+    fn set_normalized(&mut self, v: f64) {
+        self.base.set_normalized(v)
+    }
+
+    fn get_normalized(&self) -> f64 {
+        self.base.get_normalized()
+    }
+
+    fn set_precision(&mut self, val: usize) {
+        self.base.set_precision(val)
+    }
+
+    fn get_precision(&self) -> usize {
+        self.base.get_precision()
+    }
+
+    fn to_string(&self, value_normalized: f64) -> String {
+        match self
+            .program_names
+            .get(program_index(self.program_names.len(), value_normalized))
+        {
+            Some(name) => name.clone(),
+            None => String::new(),
+        }
+    }
+
+    fn from_string(&self, string: &str) -> Result<f64, ResultErr> {
+        let last = self.program_names.len().saturating_sub(1);
+        match self.program_names.iter().position(|name| name == string) {
+            Some(index) if last > 0 => Ok(index as f64 / last as f64),
+            Some(_) => Ok(0.0),
+            None => Err(InvalidArgument),
+        }
+    }
+
+    fn to_plain(&self, value_normalized: f64) -> f64 {
+        program_index(self.program_names.len(), value_normalized) as f64
+    }
+
+    fn to_normalized(&self, plain_value: f64) -> f64 {
+        let last = self.program_names.len().saturating_sub(1);
+        if last == 0 {
+            return 0.0;
+        }
+        plain_value.max(0.0).min(last as f64) / last as f64
+    }
+}
+
+pub struct UnitTree {
+    units: Vec<Unit>,
+    program_lists: Vec<ProgramList>,
+    bus_to_unit: HashMap<(i32, i32, i32, i32), i32>,
+    selected_unit: i32,
+}
+
+impl UnitTree {
+    pub fn new() -> Self {
+        let root = UnitBuilder::new("Root", ROOT_UNIT_ID)
+            .parent_unit_id(NO_PARENT_UNIT_ID)
+            .build();
+        Self {
+            units: vec![root],
+            program_lists: vec![],
+            bus_to_unit: HashMap::new(),
+            selected_unit: ROOT_UNIT_ID,
+        }
+    }
+
+    /// The parent unit and the program list referenced by `unit` must already be in the tree.
+    pub fn add_unit(&mut self, unit: Unit) -> Result<ResultOk, ResultErr> {
+        if self.get_unit(unit.id).is_some() {
+            log::trace!("UnitTree::add_unit(): duplicated unit id {}", unit.id);
+            return Err(InvalidArgument);
+        }
+        if self.get_unit(unit.parent_unit_id).is_none() {
+            log::trace!(
+                "UnitTree::add_unit(): unknown parent unit id {}",
+                unit.parent_unit_id
+            );
+            return Err(InvalidArgument);
+        }
+        if unit.program_list_id != NO_PROGRAM_LIST_ID
+            && self.get_program_list(unit.program_list_id).is_none()
+        {
+            log::trace!(
+                "UnitTree::add_unit(): unknown program list id {}",
+                unit.program_list_id
+            );
+            return Err(InvalidArgument);
+        }
+        self.units.push(unit);
+        Ok(ResOk)
+    }
+
+    /// Also adds the program change parameter of the list to `parameters`.
+    pub fn add_program_list(
+        &mut self,
+        program_list: ProgramList,
+        parameters: &mut ParameterContainer,
+    ) -> Result<ResultOk, ResultErr> {
+        if program_list.id == NO_PROGRAM_LIST_ID || self.get_program_list(program_list.id).is_some()
+        {
+            log::trace!(
+                "UnitTree::add_program_list(): invalid program list id {}",
+                program_list.id
+            );
+            return Err(InvalidArgument);
+        }
+        if parameters
+            .get_parameter(program_list.parameter_id)
+            .is_some()
+        {
+            log::trace!(
+                "UnitTree::add_program_list(): parameter id {:?} already in use",
+                program_list.parameter_id
+            );
+            return Err(InvalidArgument);
+        }
+        parameters.add_parameter(ProgramChangeParameter::new(&program_list));
+        self.program_lists.push(program_list);
+        Ok(ResOk)
+    }
+
+    pub fn get_unit_count(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn get_unit_by_index(&self, index: usize) -> Option<&Unit> {
+        self.units.get(index)
+    }
+
+    pub fn get_unit(&self, id: i32) -> Option<&Unit> {
+        self.units.iter().find(|unit| unit.id == id)
+    }
+
+    pub fn get_program_list_count(&self) -> usize {
+        self.program_lists.len()
+    }
+
+    pub fn get_program_list_by_index(&self, index: usize) -> Option<&ProgramList> {
+        self.program_lists.get(index)
+    }
+
+    pub fn get_program_list(&self, id: i32) -> Option<&ProgramList> {
+        self.program_lists.iter().find(|list| list.id == id)
+    }
+
+    pub fn get_program_list_mut(&mut self, id: i32) -> Option<&mut ProgramList> {
+        self.program_lists.iter_mut().find(|list| list.id == id)
+    }
+
+    pub fn get_program_list_by_parameter(&self, id: ParamId) -> Option<&ProgramList> {
+        self.program_lists
+            .iter()
+            .find(|list| list.parameter_id == id)
+    }
+
+    /// Accepts either a program list ID or the ID of a unit with a program list.
+    pub fn get_program_list_id(&self, list_or_unit: i32) -> Option<i32> {
+        if self.get_program_list(list_or_unit).is_some() {
+            return Some(list_or_unit);
+        }
+        match self.get_unit(list_or_unit) {
+            Some(unit) if unit.program_list_id != NO_PROGRAM_LIST_ID => Some(unit.program_list_id),
+            _ => None,
+        }
+    }
+
+    pub fn assign_parameter(
+        &self,
+        parameters: &mut ParameterContainer,
+        id: ParamId,
+        unit_id: i32,
+    ) -> Result<ResultOk, ResultErr> {
+        if self.get_unit(unit_id).is_none() {
+            return Err(InvalidArgument);
+        }
+        match parameters.get_parameter_mut(id) {
+            Some(param) => {
+                param.set_unit_id(unit_id);
+                Ok(ResOk)
+            }
+            None => Err(InvalidArgument),
+        }
+    }
+
+    /// Use a `channel` of -1 to assign every channel of the bus.
+    pub fn assign_bus(
+        &mut self,
+        media_type: MediaType,
+        dir: BusDirection,
+        bus_index: i32,
+        channel: i32,
+        unit_id: i32,
+    ) -> Result<ResultOk, ResultErr> {
+        if self.get_unit(unit_id).is_none() {
+            return Err(InvalidArgument);
+        }
+        self.bus_to_unit
+            .insert((media_type.into(), dir.into(), bus_index, channel), unit_id);
+        Ok(ResOk)
+    }
+
+    pub fn get_unit_by_bus(
+        &self,
+        media_type: i32,
+        dir: i32,
+        bus_index: i32,
+        channel: i32,
+    ) -> Option<i32> {
+        self.bus_to_unit
+            .get(&(media_type, dir, bus_index, channel))
+            .or_else(|| self.bus_to_unit.get(&(media_type, dir, bus_index, -1)))
+            .copied()
+    }
+
+    pub fn get_selected_unit(&self) -> i32 {
+        self.selected_unit
+    }
+
+    pub fn select_unit(&mut self, id: i32) -> Result<ResultOk, ResultErr> {
+        if self.get_unit(id).is_none() {
+            return Err(InvalidArgument);
+        }
+        self.selected_unit = id;
+        Ok(ResOk)
+    }
+}
+
+impl Default for UnitTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BusDirection, MediaType, ParamId, Parameter, ParameterContainer, Program,
+        ProgramChangeParameter, ProgramListBuilder, UnitBuilder, UnitTree, ROOT_UNIT_ID,
+    };
+
+    #[test]
+    fn test_program_change_parameter() {
+        let program_list = ProgramListBuilder::new("Programs", 1, ParamId(100))
+            .program(Program::new("A", vec![]))
+            .program(Program::new("B", vec![]))
+            .program(Program::new("C", vec![]))
+            .build();
+        let indices: Vec<usize> = [-1.0, 0.0, 0.49, 0.74, 1.0, 2.0]
+            .iter()
+            .map(|value| program_list.get_program_index(*value))
+            .collect();
+        assert_eq!(indices, [0, 0, 1, 1, 2, 2]);
+
+        let param = ProgramChangeParameter::new(&program_list);
+        assert_eq!(param.get_info().step_count, 2);
+        assert_eq!(param.to_string(0.5), "B");
+        assert_eq!(param.from_string("C").ok(), Some(1.0));
+        assert!(param.from_string("D").is_err());
+
+        let program_list = ProgramListBuilder::new("Programs", 1, ParamId(100))
+            .program(Program::new("A", vec![]))
+            .build();
+        let param = ProgramChangeParameter::new(&program_list);
+        assert_eq!(param.get_info().step_count, 1);
+        assert_eq!(param.to_string(1.0), "A");
+    }
+
+    #[test]
+    fn test_unit_tree() {
+        let mut parameters = ParameterContainer::new();
+        let mut tree = UnitTree::default();
+        let program_list = ProgramListBuilder::new("Programs", 1, ParamId(100))
+            .program(Program::new("Init", vec![]))
+            .build();
+        assert!(tree
+            .add_program_list(program_list.clone(), &mut parameters)
+            .is_ok());
+        assert!(parameters.get_parameter(ParamId(100)).is_some());
+        // Neither the list ID nor the parameter ID can be reused.
+        assert!(tree
+            .add_program_list(program_list, &mut parameters)
+            .is_err());
+
+        let unit = UnitBuilder::new("Synth", 1).program_list_id(1).build();
+        assert!(tree.add_unit(unit.clone()).is_ok());
+        assert!(tree.add_unit(unit).is_err());
+        assert!(tree
+            .add_unit(UnitBuilder::new("Orphan", 2).parent_unit_id(5).build())
+            .is_err());
+        assert!(tree
+            .add_unit(UnitBuilder::new("Drums", 2).program_list_id(3).build())
+            .is_err());
+        assert_eq!(tree.get_unit_count(), 2);
+        assert_eq!(tree.get_program_list_id(1), Some(1));
+        assert_eq!(tree.get_program_list_id(ROOT_UNIT_ID), None);
+
+        assert!(tree
+            .assign_bus(MediaType::Audio, BusDirection::Output, 0, -1, 1)
+            .is_ok());
+        let (audio, output) = (MediaType::Audio.into(), BusDirection::Output.into());
+        assert_eq!(tree.get_unit_by_bus(audio, output, 0, 1), Some(1));
+        assert_eq!(tree.get_unit_by_bus(audio, output, 1, 0), None);
+
+        assert!(tree.select_unit(2).is_err());
+        assert!(tree.select_unit(1).is_ok());
+        assert_eq!(tree.get_selected_unit(), 1);
+    }
 }
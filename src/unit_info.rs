@@ -1,52 +1,191 @@
 use std::os::raw::c_void;
-use vst3_sys::vst::{IUnitInfo, ProgramListInfo};
+use vst3_sys::vst::IUnitInfo;
 
+use crate::parameters::read_values;
 use crate::ResultErr::{InvalidArgument, NotImplemented, ResultFalse};
 use crate::ResultOk::ResOk;
 use crate::{
-    wstrcpy, EditController, ProgramList, ResultErr, ResultOk, Stream, Unit, Unknown,
-    VST3EditController,
+    wstrcpy, EditController, EditGroup, ParamId, Program, ProgramList, ResultErr, ResultOk, Stream,
+    Unit, UnitTree, Unknown, VST3EditController,
 };
 use std::ffi::CStr;
 
 pub trait UnitInfo: EditController {
-    fn get_unit_count(&self) -> Result<i32, ResultErr>;
-    fn get_unit_info(&self, unit_index: i32) -> Result<Unit, ResultErr>;
-    fn get_program_list_count(&self) -> Result<i32, ResultErr>;
-    fn get_program_list_info(&self, list_index: i32) -> Result<ProgramList, ResultErr>;
-    fn get_program_name(&self, list_id: i32, program_index: i32) -> Result<String, ResultErr>;
+    fn get_unit_tree(&self) -> Option<&UnitTree> {
+        None
+    }
+
+    fn get_unit_tree_mut(&mut self) -> Option<&mut UnitTree> {
+        None
+    }
+
+    fn get_unit_count(&self) -> Result<i32, ResultErr> {
+        match self.get_unit_tree() {
+            Some(tree) => Ok(tree.get_unit_count() as i32),
+            None => Err(NotImplemented),
+        }
+    }
+
+    fn get_unit_info(&self, unit_index: i32) -> Result<Unit, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        if unit_index < 0 {
+            return Err(InvalidArgument);
+        }
+        match tree.get_unit_by_index(unit_index as usize) {
+            Some(unit) => Ok(unit.clone()),
+            None => Err(InvalidArgument),
+        }
+    }
+
+    fn get_program_list_count(&self) -> Result<i32, ResultErr> {
+        match self.get_unit_tree() {
+            Some(tree) => Ok(tree.get_program_list_count() as i32),
+            None => Err(NotImplemented),
+        }
+    }
+
+    fn get_program_list_info(&self, list_index: i32) -> Result<ProgramList, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        if list_index < 0 {
+            return Err(InvalidArgument);
+        }
+        match tree.get_program_list_by_index(list_index as usize) {
+            Some(program_list) => Ok(program_list.clone()),
+            None => Err(InvalidArgument),
+        }
+    }
+
+    fn get_program_name(&self, list_id: i32, program_index: i32) -> Result<String, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        match get_program(tree, list_id, program_index) {
+            Some(program) => Ok(program.name.clone()),
+            None => Err(InvalidArgument),
+        }
+    }
+
     fn get_program_info(
         &self,
         list_id: i32,
         program_index: i32,
         attribute_id: String,
-    ) -> Result<String, ResultErr>;
-    fn has_program_pitch_names(&self, id: i32, index: i32) -> Result<ResultOk, ResultErr>;
-    fn get_program_pitch_name(&self, id: i32, index: i32, pitch: i16) -> Result<String, ResultErr>;
-    fn get_selected_unit(&self) -> Result<i32, ResultErr>;
-    fn select_unit(&self, id: i32) -> Result<ResultOk, ResultErr>;
+    ) -> Result<String, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let program = get_program(tree, list_id, program_index).ok_or(InvalidArgument)?;
+        match program.attributes.get(&attribute_id) {
+            Some(value) => Ok(value.clone()),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn has_program_pitch_names(&self, id: i32, index: i32) -> Result<ResultOk, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let program = get_program(tree, id, index).ok_or(InvalidArgument)?;
+        if program.pitch_names.is_empty() {
+            Err(ResultFalse)
+        } else {
+            Ok(ResOk)
+        }
+    }
+
+    fn get_program_pitch_name(&self, id: i32, index: i32, pitch: i16) -> Result<String, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let program = get_program(tree, id, index).ok_or(InvalidArgument)?;
+        match program.pitch_names.get(pitch) {
+            Some(name) => Ok(name.to_string()),
+            None => Err(ResultFalse),
+        }
+    }
+
+    fn get_selected_unit(&self) -> Result<i32, ResultErr> {
+        match self.get_unit_tree() {
+            Some(tree) => Ok(tree.get_selected_unit()),
+            None => Err(NotImplemented),
+        }
+    }
+
+    fn select_unit(&mut self, id: i32) -> Result<ResultOk, ResultErr> {
+        match self.get_unit_tree_mut() {
+            Some(tree) => tree.select_unit(id),
+            None => Err(NotImplemented),
+        }
+    }
+
     fn get_unit_by_bus(
         &self,
         media_type: i32,
         dir: i32,
         bus_index: i32,
         channel: i32,
-    ) -> Result<i32, ResultErr>;
+    ) -> Result<i32, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        match tree.get_unit_by_bus(media_type, dir, bus_index, channel) {
+            Some(unit_id) => Ok(unit_id),
+            None => Err(ResultFalse),
+        }
+    }
+
+    /// Reads the program values in the format written by `ParameterContainer::get_state`.
     fn set_unit_program_data(
-        &self,
+        &mut self,
         list_or_unit: i32,
         program_index: i32,
         data: Stream,
-    ) -> Result<ResultOk, ResultErr>;
+    ) -> Result<ResultOk, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let list_id = tree
+            .get_program_list_id(list_or_unit)
+            .ok_or(InvalidArgument)?;
+        let values = read_values(&data).ok_or(ResultFalse)?;
+        set_program_values(self, list_id, program_index, values)
+    }
+
+    /// Called after the host set the value of a program change parameter. Loads the values of
+    /// the selected program into the parameters; the processor receives the program change
+    /// parameter and loads the program itself.
+    fn program_parameter_changed(
+        &mut self,
+        id: ParamId,
+        value: f64,
+    ) -> Result<ResultOk, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let values = selected_program_values(tree, id, value)?;
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        parameters.apply_values(&values);
+        Ok(ResOk)
+    }
+
+    /// Selects a program from the plug-in, e.g. its editor. The program change parameter and the
+    /// values of the program are sent to the host as edits, forming one undo step.
+    fn perform_program_change(&mut self, id: ParamId, value: f64) -> Result<ResultOk, ResultErr> {
+        let tree = self.get_unit_tree().ok_or(NotImplemented)?;
+        let mut values = selected_program_values(tree, id, value)?;
+        let parameters = self.get_parameters_mut().ok_or(NotImplemented)?;
+        values.retain(|(id, _)| parameters.get_parameter(*id).is_some());
+        let mut group = EditGroup::new(parameters);
+        group.set(id, value)?;
+        for (id, value) in values {
+            group.set(id, value)?;
+        }
+        Ok(ResOk)
+    }
+}
+
+fn selected_program_values(
+    tree: &UnitTree,
+    id: ParamId,
+    value: f64,
+) -> Result<Vec<(ParamId, f64)>, ResultErr> {
+    let program_list = tree.get_program_list_by_parameter(id).ok_or(ResultFalse)?;
+    match program_list.get_program(program_list.get_program_index(value)) {
+        Some(program) => Ok(program.values.clone()),
+        None => Err(ResultFalse),
+    }
 }
 
 impl IUnitInfo for VST3EditController {
     unsafe fn get_unit_count(&self) -> i32 {
         if let Some(unit_info) = self.get_plugin_base().lock().unwrap().as_unit_info() {
-            return match unit_info.get_unit_count() {
-                Ok(unit_count) => unit_count,
-                Err(_) => 0,
-            };
+            return unit_info.get_unit_count().unwrap_or_default();
         }
         0
     }
@@ -66,10 +205,7 @@ impl IUnitInfo for VST3EditController {
 
     unsafe fn get_program_list_count(&self) -> i32 {
         if let Some(unit_info) = self.get_plugin_base().lock().unwrap().as_unit_info() {
-            return match unit_info.get_program_list_count() {
-                Ok(program_list_count) => program_list_count,
-                Err(_) => 0,
-            };
+            return unit_info.get_program_list_count().unwrap_or_default();
         }
         0
     }
@@ -160,10 +296,7 @@ impl IUnitInfo for VST3EditController {
 
     unsafe fn get_selected_unit(&self) -> i32 {
         if let Some(unit_info) = self.get_plugin_base().lock().unwrap().as_unit_info() {
-            return match unit_info.get_selected_unit() {
-                Ok(num) => num,
-                Err(r) => 0,
-            };
+            return unit_info.get_selected_unit().unwrap_or_default();
         }
         0
     }
@@ -216,3 +349,170 @@ impl IUnitInfo for VST3EditController {
         NotImplemented.into()
     }
 }
+
+pub(crate) fn get_program(tree: &UnitTree, list_id: i32, program_index: i32) -> Option<&Program> {
+    if program_index < 0 {
+        return None;
+    }
+    tree.get_program_list(list_id)?
+        .get_program(program_index as usize)
+}
+
+/// Replaces the values of a program and loads them if the program is the selected one.
+pub(crate) fn set_program_values<T: UnitInfo + ?Sized>(
+    unit_info: &mut T,
+    list_id: i32,
+    program_index: i32,
+    values: Vec<(ParamId, f64)>,
+) -> Result<ResultOk, ResultErr> {
+    if program_index < 0 {
+        return Err(InvalidArgument);
+    }
+    let tree = unit_info.get_unit_tree().ok_or(NotImplemented)?;
+    let parameter_id = tree
+        .get_program_list(list_id)
+        .ok_or(InvalidArgument)?
+        .parameter_id;
+    let current_value = unit_info
+        .get_parameters()
+        .and_then(|parameters| parameters.get_parameter(parameter_id))
+        .map(|param| param.get_normalized());
+
+    let tree = unit_info.get_unit_tree_mut().ok_or(NotImplemented)?;
+    let program_list = tree.get_program_list_mut(list_id).ok_or(InvalidArgument)?;
+    let is_selected = match current_value {
+        Some(value) => program_list.get_program_index(value) == program_index as usize,
+        None => false,
+    };
+    match program_list.get_program_mut(program_index as usize) {
+        Some(program) => program.values = values.clone(),
+        None => return Err(InvalidArgument),
+    }
+
+    if is_selected {
+        if let Some(parameters) = unit_info.get_parameters_mut() {
+            parameters.apply_values(&values);
+        }
+    }
+    Ok(ResOk)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unit_info::set_program_values;
+    use crate::{
+        BaseParameter, EditController, EditHistory, HostApplication, ParamId, ParameterContainer,
+        ParameterInfoBuilder, PlugView, PluginBase, Program, ProgramListBuilder, UnitInfo,
+        UnitTree,
+    };
+
+    const PROGRAM_PARAM: ParamId = ParamId(100);
+
+    struct TestController {
+        parameters: ParameterContainer,
+        tree: UnitTree,
+    }
+
+    impl PluginBase for TestController {
+        fn initialize(&mut self, _context: HostApplication) -> bool {
+            true
+        }
+
+        fn terminate(&mut self) -> bool {
+            true
+        }
+    }
+
+    impl EditController for TestController {
+        fn get_parameters(&self) -> Option<&ParameterContainer> {
+            Some(&self.parameters)
+        }
+
+        fn get_parameters_mut(&mut self) -> Option<&mut ParameterContainer> {
+            Some(&mut self.parameters)
+        }
+
+        fn create_view(&mut self) -> Option<&mut Box<dyn PlugView>> {
+            None
+        }
+    }
+
+    impl UnitInfo for TestController {
+        fn get_unit_tree(&self) -> Option<&UnitTree> {
+            Some(&self.tree)
+        }
+
+        fn get_unit_tree_mut(&mut self) -> Option<&mut UnitTree> {
+            Some(&mut self.tree)
+        }
+    }
+
+    fn controller() -> TestController {
+        let mut parameters = ParameterContainer::new();
+        for id in 0..2 {
+            let info = ParameterInfoBuilder::new("Param", ParamId(id)).build();
+            parameters.add_parameter(BaseParameter::new(info));
+        }
+        let program_list = ProgramListBuilder::new("Programs", 1, PROGRAM_PARAM)
+            .program(Program::new(
+                "Init",
+                vec![(ParamId(0), 0.0), (ParamId(1), 0.0)],
+            ))
+            .program(Program::new(
+                "Lead",
+                vec![(ParamId(0), 0.5), (ParamId(1), 1.0)],
+            ))
+            .build();
+        let mut tree = UnitTree::new();
+        assert!(tree.add_program_list(program_list, &mut parameters).is_ok());
+        TestController { parameters, tree }
+    }
+
+    fn values(controller: &TestController) -> (f64, f64) {
+        let value = |id| match controller.parameters.get_parameter(ParamId(id)) {
+            Some(param) => param.get_normalized(),
+            None => -1.0,
+        };
+        (value(0), value(1))
+    }
+
+    #[test]
+    fn test_program_parameter_changed() {
+        let mut controller = controller();
+        assert!(controller
+            .program_parameter_changed(PROGRAM_PARAM, 1.0)
+            .is_ok());
+        assert_eq!(values(&controller), (0.5, 1.0));
+        assert!(controller
+            .program_parameter_changed(ParamId(0), 1.0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_program_values() {
+        let mut controller = controller();
+        // Only the selected program is loaded into the parameters.
+        assert!(set_program_values(&mut controller, 1, 1, vec![(ParamId(0), 0.25)]).is_ok());
+        assert_eq!(values(&controller), (0.0, 0.0));
+        assert!(set_program_values(&mut controller, 1, 0, vec![(ParamId(1), 0.75)]).is_ok());
+        assert_eq!(values(&controller), (0.0, 0.75));
+        assert!(set_program_values(&mut controller, 1, 2, vec![]).is_err());
+        assert!(set_program_values(&mut controller, 2, 0, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_perform_program_change() {
+        let mut controller = controller();
+        controller
+            .parameters
+            .set_edit_history(Some(EditHistory::new(8)));
+        assert!(controller
+            .perform_program_change(PROGRAM_PARAM, 1.0)
+            .is_ok());
+        assert_eq!(values(&controller), (0.5, 1.0));
+        assert!(controller.parameters.undo().is_ok());
+        assert_eq!(values(&controller), (0.0, 0.0));
+        let history = controller.parameters.get_edit_history().unwrap();
+        assert_eq!((history.get_undo_count(), history.get_redo_count()), (0, 1));
+    }
+}
@@ -7,6 +7,7 @@ pub trait Unknown {
     fn from_raw(ptr: *mut c_void) -> Option<Box<Self>>;
 }
 
+#[derive(Debug)]
 pub enum ResultOk {
     ResOk,
     ResTrue,
@@ -21,6 +22,7 @@ impl From<ResultOk> for i32 {
     }
 }
 
+#[derive(Debug)]
 pub enum ResultErr {
     NoInterface,
     ResultFalse,
@@ -0,0 +1,410 @@
+use std::convert::TryFrom;
+
+use num_traits::Float;
+
+use crate::{
+    Event, MpeDimension, NoteExpressionKind, NoteModulation, Outputs, ParamId, ProcessData,
+};
+
+/// A single voice of an instrument. `render` adds the voice to the outputs, which only cover the
+/// current sub-block.
+pub trait Voice {
+    /// `tuning` is in cents.
+    fn note_on(&mut self, pitch: i16, velocity: f32, tuning: f32);
+    fn note_off(&mut self, velocity: f32);
+    /// Returns `false` once the voice has finished its release and can be reused.
+    fn is_active(&self) -> bool;
+    fn render<T: Float>(&mut self, outputs: &mut Outputs<T>);
+
+    /// Called for the note expression values sent to the note of this voice.
+    fn note_expression(&mut self, _kind: NoteExpressionKind, _value: f64) {}
+
+    /// Called for the MPE modulations of the note of this voice.
+    fn note_modulation(&mut self, _dimension: MpeDimension, _value: f64) {}
+
+    fn reset(&mut self) {}
+}
+
+/// Which voice is taken for a new note when all voices are in use.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StealPolicy {
+    /// New notes are dropped.
+    NoStealing,
+    Oldest,
+    LowestPitch,
+    HighestPitch,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum VoiceState {
+    Idle,
+    Held,
+    Sustained,
+    Released,
+}
+
+struct VoiceSlot<V> {
+    voice: V,
+    state: VoiceState,
+    note_id: i32,
+    channel: i16,
+    pitch: i16,
+    age: u64,
+}
+
+pub struct VoiceManager<V: Voice> {
+    slots: Vec<VoiceSlot<V>>,
+    steal_policy: StealPolicy,
+    sustain_parameter: Option<ParamId>,
+    sustain: bool,
+    note_counter: u64,
+}
+
+impl<V: Voice> VoiceManager<V> {
+    pub fn new(polyphony: usize, mut make_voice: impl FnMut() -> V) -> Self {
+        Self {
+            slots: (0..polyphony)
+                .map(|_| VoiceSlot {
+                    voice: make_voice(),
+                    state: VoiceState::Idle,
+                    note_id: -1,
+                    channel: 0,
+                    pitch: 0,
+                    age: 0,
+                })
+                .collect(),
+            steal_policy: StealPolicy::Oldest,
+            sustain_parameter: None,
+            sustain: false,
+            note_counter: 0,
+        }
+    }
+
+    pub fn set_steal_policy(&mut self, steal_policy: StealPolicy) {
+        self.steal_policy = steal_policy;
+    }
+
+    /// Parameter mapped to the sustain pedal. Hosts do not send MIDI controllers as events: map
+    /// CC 64 to a hidden parameter through `MidiMapping`, e.g. with
+    /// `MidiMappingTable::add_proxy_parameter`, and its changes in `process` set the sustain.
+    pub fn set_sustain_parameter(&mut self, id: Option<ParamId>) {
+        self.sustain_parameter = id;
+    }
+
+    pub fn get_polyphony(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn get_active_voice_count(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.state != VoiceState::Idle)
+            .count()
+    }
+
+    pub fn voices_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.slots.iter_mut().map(|slot| &mut slot.voice)
+    }
+
+    pub fn note_on(&mut self, channel: i16, pitch: i16, velocity: f32, tuning: f32, note_id: i32) {
+        let index = match self.find_free_slot().or_else(|| self.find_slot_to_steal()) {
+            Some(index) => index,
+            None => return,
+        };
+        self.note_counter += 1;
+        let slot = &mut self.slots[index];
+        if slot.state != VoiceState::Idle {
+            slot.voice.reset();
+        }
+        slot.voice.note_on(pitch, velocity, tuning);
+        slot.state = VoiceState::Held;
+        slot.note_id = note_id;
+        slot.channel = channel;
+        slot.pitch = pitch;
+        slot.age = self.note_counter;
+    }
+
+    /// Voices are matched by `note_id` when it is not -1, otherwise by channel and pitch.
+    pub fn note_off(&mut self, channel: i16, pitch: i16, velocity: f32, note_id: i32) {
+        let sustain = self.sustain;
+        for slot in self.slots.iter_mut() {
+            if slot.state != VoiceState::Held {
+                continue;
+            }
+            let matches = if note_id != -1 {
+                slot.note_id == note_id
+            } else {
+                slot.channel == channel && slot.pitch == pitch
+            };
+            if !matches {
+                continue;
+            }
+            if sustain {
+                slot.state = VoiceState::Sustained;
+            } else {
+                slot.voice.note_off(velocity);
+                slot.state = VoiceState::Released;
+            }
+        }
+    }
+
+    /// Sends a note expression value to the voice playing `note_id`.
+    pub fn note_expression(&mut self, note_id: i32, kind: NoteExpressionKind, value: f64) {
+        if note_id == -1 {
+            return;
+        }
+        for slot in self.slots.iter_mut() {
+            if slot.state != VoiceState::Idle && slot.note_id == note_id {
+                slot.voice.note_expression(kind, value);
+            }
+        }
+    }
+
+    /// Sends a modulation to the voice playing its note, matched like in `note_off`.
+    pub fn note_modulation(&mut self, modulation: &NoteModulation) {
+        for slot in self.slots.iter_mut() {
+            if slot.state == VoiceState::Idle {
+                continue;
+            }
+            let matches = if modulation.note_id != -1 {
+                slot.note_id == modulation.note_id
+            } else {
+                slot.channel == modulation.channel && slot.pitch == modulation.pitch
+            };
+            if matches {
+                slot.voice
+                    .note_modulation(modulation.dimension, modulation.value);
+            }
+        }
+    }
+
+    pub fn set_sustain(&mut self, sustain: bool) {
+        self.sustain = sustain;
+        if sustain {
+            return;
+        }
+        for slot in self.slots.iter_mut() {
+            if slot.state == VoiceState::Sustained {
+                slot.voice.note_off(0.0);
+                slot.state = VoiceState::Released;
+            }
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        self.sustain = false;
+        for slot in self.slots.iter_mut() {
+            if slot.state == VoiceState::Held || slot.state == VoiceState::Sustained {
+                slot.voice.note_off(0.0);
+                slot.state = VoiceState::Released;
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.sustain = false;
+        for slot in self.slots.iter_mut() {
+            slot.voice.reset();
+            slot.state = VoiceState::Idle;
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::NoteOn {
+                channel,
+                pitch,
+                tuning,
+                velocity,
+                note_id,
+                ..
+            } => self.note_on(channel, pitch, velocity, tuning, note_id),
+            Event::NoteOff {
+                channel,
+                pitch,
+                velocity,
+                note_id,
+                ..
+            } => self.note_off(channel, pitch, velocity, note_id),
+            Event::NoteExpressionValue {
+                type_id,
+                note_id,
+                value,
+                ..
+            } => {
+                if let Ok(kind) = NoteExpressionKind::try_from(type_id) {
+                    self.note_expression(note_id, kind, value)
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Renders the whole block, handling events and parameter changes at their sample offsets.
+    /// Changes of parameters other than the sustain parameter are passed to `param_changed`.
+    pub fn process<T: Float>(
+        &mut self,
+        data: &mut ProcessData<T>,
+        mut param_changed: impl FnMut(ParamId, f64),
+    ) {
+        let has_outputs = data.num_outputs() > 0;
+        for mut block in data.sub_blocks() {
+            for (id, value) in block.param_changes() {
+                if Some(id) == self.sustain_parameter {
+                    self.set_sustain(value >= 0.5);
+                } else {
+                    param_changed(id, value);
+                }
+            }
+            for event in block.events() {
+                self.handle_event(&event);
+            }
+            if !has_outputs || block.len == 0 {
+                continue;
+            }
+
+            for bus in 0..block.outputs.len() {
+                if let Some(mut output) = block.outputs.get_mut(bus) {
+                    for channel in output.iter_mut() {
+                        for sample in channel.iter_mut() {
+                            *sample = T::zero();
+                        }
+                    }
+                }
+            }
+            self.render(&mut block.outputs);
+        }
+    }
+
+    /// Adds all active voices to `outputs`.
+    pub fn render<T: Float>(&mut self, outputs: &mut Outputs<T>) {
+        for slot in self.slots.iter_mut() {
+            if slot.state == VoiceState::Idle {
+                continue;
+            }
+            slot.voice.render(outputs);
+            if !slot.voice.is_active() {
+                slot.state = VoiceState::Idle;
+            }
+        }
+    }
+
+    fn find_free_slot(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.state == VoiceState::Idle)
+    }
+
+    fn find_slot_to_steal(&self) -> Option<usize> {
+        // Released voices are stolen first, then sustained ones, then held ones.
+        let priority = |slot: &VoiceSlot<V>| match slot.state {
+            VoiceState::Released => 0,
+            VoiceState::Sustained => 1,
+            _ => 2,
+        };
+        let candidates = self.slots.iter().enumerate();
+        let steal = match self.steal_policy {
+            StealPolicy::NoStealing => return None,
+            StealPolicy::Oldest => candidates.min_by_key(|(_, slot)| (priority(slot), slot.age)),
+            StealPolicy::LowestPitch => {
+                candidates.min_by_key(|(_, slot)| (priority(slot), slot.pitch))
+            }
+            StealPolicy::HighestPitch => {
+                candidates.min_by_key(|(_, slot)| (priority(slot), -slot.pitch))
+            }
+        };
+        steal.map(|(index, _)| index)
+    }
+}
+
+/// Frequency in Hz of a MIDI pitch detuned by `tuning` cents.
+pub fn pitch_to_frequency(pitch: i16, tuning: f32) -> f64 {
+    440.0 * 2.0f64.powf((pitch as f64 - 69.0 + tuning as f64 / 100.0) / 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::Float;
+
+    use crate::{Outputs, StealPolicy, Voice, VoiceManager};
+
+    #[derive(Default)]
+    struct TestVoice {
+        pitch: i16,
+        released: bool,
+    }
+
+    impl Voice for TestVoice {
+        fn note_on(&mut self, pitch: i16, _velocity: f32, _tuning: f32) {
+            self.pitch = pitch;
+            self.released = false;
+        }
+
+        fn note_off(&mut self, _velocity: f32) {
+            self.released = true;
+        }
+
+        fn is_active(&self) -> bool {
+            true
+        }
+
+        fn render<T: Float>(&mut self, _outputs: &mut Outputs<T>) {}
+    }
+
+    fn pitches(manager: &mut VoiceManager<TestVoice>) -> Vec<(i16, bool)> {
+        let mut pitches: Vec<(i16, bool)> = manager
+            .voices_mut()
+            .map(|voice| (voice.pitch, voice.released))
+            .collect();
+        pitches.sort();
+        pitches
+    }
+
+    #[test]
+    fn test_note_id_matching() {
+        let mut manager = VoiceManager::new(2, TestVoice::default);
+        manager.note_on(0, 60, 1.0, 0.0, 1);
+        manager.note_on(0, 60, 1.0, 0.0, 2);
+        manager.note_off(0, 60, 0.0, 2);
+        assert_eq!(pitches(&mut manager), vec![(60, false), (60, true)]);
+        // Without a note ID, voices are matched by channel and pitch.
+        manager.note_off(1, 60, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(60, false), (60, true)]);
+        manager.note_off(0, 60, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(60, true), (60, true)]);
+    }
+
+    #[test]
+    fn test_stealing_order() {
+        let mut manager = VoiceManager::new(2, TestVoice::default);
+        manager.note_on(0, 62, 1.0, 0.0, -1);
+        manager.note_on(0, 60, 1.0, 0.0, -1);
+        manager.note_on(0, 64, 1.0, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(60, false), (64, false)]);
+
+        manager.set_steal_policy(StealPolicy::HighestPitch);
+        manager.note_on(0, 65, 1.0, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(60, false), (65, false)]);
+
+        // Released voices go first, whatever the policy.
+        manager.note_off(0, 60, 0.0, -1);
+        manager.note_on(0, 67, 1.0, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(65, false), (67, false)]);
+
+        manager.set_steal_policy(StealPolicy::NoStealing);
+        manager.note_on(0, 69, 1.0, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(65, false), (67, false)]);
+    }
+
+    #[test]
+    fn test_sustain() {
+        let mut manager = VoiceManager::new(2, TestVoice::default);
+        manager.set_sustain(true);
+        manager.note_on(0, 60, 1.0, 0.0, -1);
+        manager.note_off(0, 60, 0.0, -1);
+        assert_eq!(pitches(&mut manager), vec![(0, false), (60, false)]);
+        manager.set_sustain(false);
+        assert_eq!(pitches(&mut manager), vec![(0, false), (60, true)]);
+        assert_eq!(manager.get_active_voice_count(), 1);
+    }
+}